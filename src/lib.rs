@@ -0,0 +1,5066 @@
+//! minissg's markdown parser and renderer, plus the site build behind the `minissg` binary.
+//!
+//! [`parse`] turns markdown into [`Block`]s and [`render_document`] turns those into html;
+//! [`compile_all`] and friends build a whole site from a [`CompilerConfig`] read by [`load_config`].
+//!
+//! [`Block`], [`Text`], [`TextFormat`], [`ListItem`], [`DefinitionItem`] and [`Alignment`] are
+//! public so downstream code can walk and match on a parsed post; adding a variant or field to
+//! any of them is a breaking change.
+
+use std::cell::Cell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Read;
+use std::iter::Peekable;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::str::{Chars, Lines};
+use std::sync::{Arc, LazyLock, Mutex, OnceLock};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+use log::{debug, info};
+use notify::{RecursiveMode, Watcher};
+use rayon::prelude::*;
+use regex::Regex;
+use serde::Deserialize;
+
+// counted so --strict can fail the build on any warning/error
+static WARNINGS: AtomicUsize = AtomicUsize::new(0);
+static ERRORS: AtomicUsize = AtomicUsize::new(0);
+
+/// (warnings, errors) reported so far
+pub fn diagnostic_counts() -> (usize, usize) {
+    (WARNINGS.load(Ordering::Relaxed), ERRORS.load(Ordering::Relaxed))
+}
+
+// a post renders on one thread; anything reported while it does keeps the render out of the cache
+thread_local! {
+    static RENDER_ISSUES: Cell<usize> = const { Cell::new(0) };
+}
+
+// one lock per math cache entry, so parallel posts compile a shared expression once
+static MATH_IN_FLIGHT: LazyLock<Mutex<BTreeMap<PathBuf, Arc<Mutex<()>>>>> = LazyLock::new(Default::default);
+
+// the link and bare url patterns are also matched anchored, at the parser's position
+const LINK_PATTERN: &str = r#"\[([^\]]*)\]\(([^)"]*?)(?:\s+"([^"]*)")?\s*\)"#;
+// `*` and `~` end a url so a closing `**` or `~~` isn't taken into it
+const BARE_URL_PATTERN: &str = r"https?://[^\s<>*~]+";
+
+static IMAGE_REGEX: LazyLock<Regex> = LazyLock::new(||
+    Regex::new(r"!\[([^\]]*)\]\(([^)]*)\)(?:\{(\d+)([a-z]+|%)?\})?").unwrap());
+static FOOTNOTE_DEFN_REGEX: LazyLock<Regex> = LazyLock::new(||
+    Regex::new(r"^\[\^(\d+)\]:\s*(.*)").unwrap());
+static OL_LI_REGEX: LazyLock<Regex> = LazyLock::new(||
+    Regex::new(r"^( *)([^\s.)]+)([.)])\s+(.*)").unwrap());
+static UL_LI_REGEX: LazyLock<Regex> = LazyLock::new(||
+    Regex::new(r"^( *)([-*])\s+(.*)").unwrap());
+static LINK_REGEX: LazyLock<Regex> = LazyLock::new(||
+    Regex::new(LINK_PATTERN).unwrap());
+static LINK_START_REGEX: LazyLock<Regex> = LazyLock::new(||
+    Regex::new(&format!("^(?:{})", LINK_PATTERN)).unwrap());
+static SVG_JUNK_REGEX: LazyLock<Regex> = LazyLock::new(||
+    Regex::new(r"(?s)<\?xml.*?\?>|<!--.*?-->|<metadata>.*?</metadata>").unwrap());
+static SVG_DECIMAL_REGEX: LazyLock<Regex> = LazyLock::new(||
+    Regex::new(r"(\d+\.\d{3})\d+").unwrap());
+static SVG_GAP_REGEX: LazyLock<Regex> = LazyLock::new(||
+    Regex::new(r">\s+<").unwrap());
+static FOOTNOTE_REF_REGEX: LazyLock<Regex> = LazyLock::new(||
+    Regex::new(r"\[\^(\d+)\]").unwrap());
+static ENTITY_REGEX: LazyLock<Regex> = LazyLock::new(||
+    Regex::new(r"^&(?:#\d+|#[xX][0-9a-fA-F]+|[a-zA-Z][a-zA-Z0-9]*);").unwrap());
+static SPACE_RUN_REGEX: LazyLock<Regex> = LazyLock::new(||
+    Regex::new(r" {2,}").unwrap());
+static CITATION_REGEX: LazyLock<Regex> = LazyLock::new(||
+    Regex::new(r"\[@([\w:./-]+)\]").unwrap());
+static BARE_URL_REGEX: LazyLock<Regex> = LazyLock::new(||
+    Regex::new(BARE_URL_PATTERN).unwrap());
+static BARE_URL_START_REGEX: LazyLock<Regex> = LazyLock::new(||
+    Regex::new(&format!("^(?:{})", BARE_URL_PATTERN)).unwrap());
+static SHORTCODE_REGEX: LazyLock<Regex> = LazyLock::new(||
+    Regex::new(r"^\{\{\s*(\w+):\s*([\w-]+)\s*\}\}$").unwrap());
+static PLACEHOLDER_REGEX: LazyLock<Regex> = LazyLock::new(||
+    Regex::new(r"\{\{([\w-]+)\}\}").unwrap());
+static HEADER_CODE_REGEX: LazyLock<Regex> = LazyLock::new(||
+    Regex::new(r"`([^`]+)`").unwrap());
+
+/// how a run of inline text renders; see the crate docs on matching on parsed posts
+#[derive(Debug, PartialEq, Clone)]
+pub enum TextFormat {
+    Raw,                // first parsing pass, math
+    Plain,
+    Bold,
+    Italic,
+    InlineMath,
+    InlineCode,
+    FootnoteRef,
+    FootnoteSeparator,  // between back to back refs, e.g. [^1][^2]; see footnote_ref_separator
+    Citation,           // src is the bibliography key
+    Strikethrough,
+    Link(String, Option<String>),  // URL, `"title"`
+}
+
+/// a run of inline text in one format
+#[derive(Debug)]
+pub struct Text {
+    pub src: String,
+    pub fmt: TextFormat,
+    pub marks: Vec<TextFormat>,     // enclosing bold/italic/strikethrough, outermost first
+}
+
+/// one item of a [`Block::List`]
+#[derive(Debug)]
+pub struct ListItem {
+    // marker: String,
+    pub level: usize,
+    pub delimiter: char,    // `.` or `)` after an ordered marker, `-` or `*` for unordered
+    pub content: Vec<Text>,
+    pub blocks: Vec<Block>,     // nested blocks (e.g. fenced code) under the item
+}
+
+/// one or more terms sharing one or more `: ` definitions
+#[derive(Debug)]
+pub struct DefinitionItem {
+    pub terms: Vec<Vec<Text>>,
+    pub definitions: Vec<Vec<Text>>,
+}
+
+/// a top-level piece of a post, as returned by [`parse`]
+#[derive(Debug)]
+pub enum Block {
+    Paragraph(Vec<Text>),
+    Header(usize, String),       // level, source
+    Code(String, String, Option<String>),  // language, source, filename caption
+    Math(String),
+    Image(String, String, u32, String),  // alt, url, width, css unit (`%` unless given)
+    Html(String),
+    Quote(Vec<(usize, Vec<Text>)>),  // (depth, text) per line of a contiguous quote
+    Footnote(String, Vec<Text>), // id, text
+    List(bool, Vec<ListItem>),   // true = ordered, false = unordered
+    DefinitionList(Vec<DefinitionItem>),
+    Container {                  // `:::name` ... `:::`
+        name: String,
+        content: Vec<Block>,
+    },
+    FootnoteFlush,               // `[^]`, see FootnotePlacement::Marker
+    Toc,                         // `[[TOC]]`
+    Lof,                         // `{{lof}}`, list of figures
+    Table {
+        headers: Vec<Vec<Text>>,
+        rows: Vec<Vec<Vec<Text>>>,
+        align: Vec<Alignment>,  // one per column
+    },
+}
+
+/// a table column's alignment, from its `:---:` delimiter
+#[derive(Debug, PartialEq)]
+pub enum Alignment {
+    None,
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct FrontMatter {
+    title: Option<String>,
+    code_lang_map: BTreeMap<String, String>,  // per-post fence language overrides, "*" forces all
+    tags: Vec<String>,
+    draft: bool,                // compiled, but left out of site-wide listings
+    dir: Option<String>,        // text direction, e.g. rtl
+    #[serde(flatten)]
+    extra: BTreeMap<String, toml::Value>,  // anything else, exposed as {{key}} in the template
+}
+
+// minissg.toml; every key is optional and falls back to the built-in layout
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    posts_dir: Option<PathBuf>,
+    images_dir: Option<PathBuf>,
+    output_dir: Option<PathBuf>,
+    post_template: Option<PathBuf>,
+    math_template: Option<PathBuf>,
+    index_template: Option<PathBuf>,
+    tag_template: Option<PathBuf>,
+    rss_template: Option<PathBuf>,
+    atom_template: Option<PathBuf>,
+    sitemap_template: Option<PathBuf>,
+    site_url: Option<String>,
+    site_title: Option<String>,
+    bibliography: Option<PathBuf>,  // `key: reference text` lines
+    images_url: Option<String>,
+    posts_url: Option<String>,
+    cache_dir: Option<PathBuf>,
+    // everything below mirrors the CompilerConfig field of the same name
+    math_font_package: Option<String>,
+    optimize_svg: Option<bool>,
+    collapsible_sections: Option<bool>,
+    max_list_depth: Option<usize>,
+    code_copy_button: Option<bool>,
+    math_backend: Option<MathBackend>,
+    merge_subtitle: Option<bool>,
+    embed_source: Option<bool>,
+    code_wrap: Option<CodeWrap>,
+    alt_text_policy: Option<AltTextPolicy>,
+    number_equations: Option<bool>,
+    number_figures: Option<bool>,
+    output_format: Option<OutputFormat>,
+    nav_links: Option<Vec<(String, String)>>,  // [["home", "/"], ...]
+    code_lang_aliases: Option<BTreeMap<String, String>>,
+    lead_paragraph: Option<bool>,
+    show_code_lang: Option<bool>,
+    math_noscript_fallback: Option<bool>,
+    footnote_id_prefix: Option<bool>,
+    footnote_backref_symbol: Option<String>,
+    footnote_ref_separator: Option<String>,
+    image_thumbnails: Option<u32>,
+    preserve_spaces: Option<bool>,
+    math_svg_class: Option<String>,
+    strict_front_matter: Option<bool>,
+    edit_url_template: Option<String>,
+    math_error_display: Option<MathErrorDisplay>,
+    excerpt_words: Option<usize>,
+    indent_width: Option<usize>,
+    title_from_h1: Option<bool>,
+    footnote_placement: Option<FootnotePlacement>,
+    math_timeout: Option<u64>,  // seconds
+    containers: Option<BTreeMap<String, ContainerSpec>>,  // [containers.note] tag = "aside", class = "note"
+    code_tabs: Option<bool>,
+    slug_from_title: Option<bool>,
+    emit_sidecar: Option<bool>,
+    back_to_top: Option<bool>,
+    syntax_highlighting: Option<bool>,
+    autodetect_code_lang: Option<bool>,
+    demote_extra_h1s: Option<bool>,
+    math_cache_scope: Option<MathCacheScope>,
+    on_post_error: Option<PostErrorPolicy>,
+    display_math_align: Option<DisplayMathAlign>,
+    empty_list_items: Option<EmptyListItemPolicy>,
+}
+
+// what compile_all needs to know about a compiled post
+#[derive(Debug)]
+struct PostMeta {
+    title: String,
+    url: String,
+    date: Option<String>,       // front matter `date`, as written
+    tags: Vec<String>,
+    draft: bool,
+    text: String,               // plain-text rendering of the body
+    excerpt: String,            // html, see excerpt()
+    summary: String,            // plain text of the first paragraph, for feeds
+    modified: Option<SystemTime>,  // source mtime, the feed date when front matter has none
+}
+
+// in minissg.toml as the snake_case variant name
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum MathBackend {
+    Svg,                // latex + dvisvgm
+    #[serde(rename = "mathml")]
+    MathMl,             // latexmlmath, falls back to svg on failure
+    Client,             // raw \(..\) for katex/mathjax in the browser
+    Off,                // --no-math: formulas shown as source, nothing shelled out
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum CodeWrap {
+    Scroll,             // long lines scroll horizontally, the stylesheet default
+    Wrap,               // pre.code-wrap, long lines wrap
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum AltTextPolicy {
+    Warn,               // log images without alt text
+    Error,              // report them as errors (fails the build under --strict)
+    Derive,             // generate alt text from the file name
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum EmptyListItemPolicy {
+    Skip,               // drop `- ` items with nothing after the marker
+    Keep,               // keep them as an empty <li>
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum OutputFormat {
+    Html,
+    PlainText,          // readable .txt, e.g. for email digests
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum DisplayMathAlign {
+    Center,
+    Left,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum PostErrorPolicy {
+    Skip,               // report the post and carry on with the rest
+    Abort,              // stop the build at the first broken post
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum MathCacheScope {
+    #[serde(alias = "per-post")]
+    PerPost,            // cache_dir/math/<slug>/, nothing is shared between posts
+    Global,             // cache_dir/math/, an expression compiled once serves every post
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum MathErrorDisplay {
+    Code,               // the latex error, in a <code>
+    Source,             // the original $...$ so the page still reads naturally
+    Hidden,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum FootnotePlacement {
+    #[serde(alias = "none")]
+    InPlace,            // where they're defined
+    End,                // collected at the end of the document
+    Marker,             // collected until a `[^]` line, leftovers at the end
+}
+
+// what a `:::name` container renders as
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ContainerSpec {
+    tag: String,
+    class: String,
+}
+
+/// everything that can stop a post (or the whole build) from compiling
+#[derive(Debug)]
+pub enum MinissgError {
+    Io { path: PathBuf, source: std::io::Error },   // a file that couldn't be read
+    Config { path: PathBuf, message: String },      // minissg.toml or a template that can't be used
+    Command { program: String, message: String },   // an external tool that couldn't run or timed out
+    Math(String),                                   // latex/latexmlmath rejected an expression
+    ToolMissing(String),                            // an external tool that isn't installed
+    Watch(String),                                  // the file watcher couldn't start
+    Serve { port: u16, source: std::io::Error },    // the dev server couldn't listen
+}
+
+impl std::fmt::Display for MinissgError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            MinissgError::Io { path, source } => write!(f, "could not read {}: {}", path.display(), source),
+            MinissgError::Config { path, message } => write!(f, "invalid config {}: {}", path.display(), message),
+            MinissgError::Command { program, message } => write!(f, "could not run {}: {}", program, message),
+            MinissgError::Math(message) => write!(f, "{}", message),
+            MinissgError::ToolMissing(program) => write!(f, "{} not found in PATH; install TeX or disable math", program),
+            MinissgError::Watch(message) => write!(f, "could not start watching: {}", message),
+            MinissgError::Serve { port, source } => write!(f, "could not listen on port {}: {}", port, source),
+        }
+    }
+}
+
+impl std::error::Error for MinissgError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MinissgError::Io { source, .. } | MinissgError::Serve { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+// page skeletons, all filled in by fill_template
+#[derive(Debug)]
+struct Templates {
+    post: String,
+    index: Option<String>,      // the post list; the post template when unset
+    tag: Option<String>,        // one tag's posts; the post template when unset
+    rss: String,
+    atom: String,
+    sitemap: String,
+}
+
+// the files the configured Templates came from, for watch to reload; None is a built-in
+#[derive(Debug, Default)]
+struct TemplateSources {
+    index: Option<PathBuf>,
+    tag: Option<PathBuf>,
+    rss: Option<PathBuf>,
+    atom: Option<PathBuf>,
+    sitemap: Option<PathBuf>,
+}
+
+// used when there's no templates/template.html and post_template isn't set
+const DEFAULT_POST_TEMPLATE: &str = "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>{{title}}</title>
+</head>
+<body>
+<header>{{nav}}</header>
+<main>
+{{content}}
+</main>
+</body>
+</html>
+";
+
+const DEFAULT_MATH_TEMPLATE: &str = "\\documentclass[border=3pt]{standalone}
+\\usepackage{amsmath,amssymb}
+\\begin{document}
+{{content}}
+\\end{document}
+";
+
+const DEFAULT_RSS_TEMPLATE: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<rss version=\"2.0\">
+<channel>
+<title>{{title}}</title>
+<link>{{link}}</link>
+<description>{{title}}</description>
+{{items}}</channel>
+</rss>
+";
+
+const DEFAULT_ATOM_TEMPLATE: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<feed xmlns=\"http://www.w3.org/2005/Atom\">
+<title>{{title}}</title>
+<link href=\"{{link}}\"/>
+<id>{{link}}</id>
+<updated>{{updated}}</updated>
+{{entries}}</feed>
+";
+
+const DEFAULT_SITEMAP_TEMPLATE: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">
+{{urls}}</urlset>
+";
+
+/// the site settings, built by [`load_config`] or [`CompilerConfig::default`]; fields stay
+/// private so new settings aren't breaking changes
+#[derive(Debug)]
+pub struct CompilerConfig {
+    posts_dir: PathBuf,
+    images_url: String,         // url prefix for emitted <img src>
+    images_dir: PathBuf,        // where those images live on disk
+    output_dir: PathBuf,
+    cache_dir: PathBuf,         // rendered posts, keyed by content hash
+    templates: Templates,
+    math_template: String,
+    math_font_package: Option<String>,  // e.g. newtxmath, \usepackage'd into the math preamble
+    optimize_svg: bool,         // minify dvisvgm output before embedding
+    collapsible_sections: bool, // wrap each h2 section in <details>
+    max_list_depth: usize,      // deeper list items are flattened to this level
+    code_copy_button: bool,     // wrap code blocks with a copy button for js to hook
+    math_backend: MathBackend,
+    merge_subtitle: bool,       // render an h1 directly followed by an h2 as one title block
+    embed_source: bool,         // append the markdown source as an html comment
+    code_wrap: CodeWrap,
+    alt_text_policy: AltTextPolicy,
+    bibliography: BTreeMap<String, String>,  // citation key => reference text
+    number_equations: bool,     // right-aligned (n) after each display equation
+    number_figures: bool,       // captioned images become numbered <figure>s, listed by {{lof}}
+    output_format: OutputFormat,
+    posts_url: String,          // url prefix posts are served under
+    site_url: String,           // absolute base for feed and sitemap links, e.g. https://example.com
+    site_title: String,         // feed channel title
+    nav_links: Vec<(String, String)>,  // label, url
+    code_lang_aliases: BTreeMap<String, String>,  // fence language => highlight class
+    lead_paragraph: bool,       // class="lead" on the first paragraph
+    show_code_lang: bool,       // label code blocks with their language
+    math_noscript_fallback: bool,  // client backend also emits svg in <noscript>
+    footnote_id_prefix: bool,   // lead footnote definitions with a linked [id]
+    footnote_backref_symbol: String,  // trailing link back to the reference, empty for none
+    footnote_ref_separator: String,   // between adjacent references, empty for none
+    image_thumbnails: Option<u32>,  // max thumbnail edge in px; larger images get one
+    preserve_spaces: bool,      // runs of 2+ spaces in text become &nbsp;
+    math_svg_class: Option<String>,  // added to the root <svg> of rendered math
+    strict_front_matter: bool,  // warn on front matter keys nothing uses
+    edit_url_template: Option<String>,  // {{file}} => source file name, fills {{edit_url}}
+    math_error_display: MathErrorDisplay,
+    excerpt_words: usize,       // length of {{excerpt}}
+    indent_width: usize,        // spaces per list nesting level
+    title_from_h1: bool,        // untitled posts take their first h1 as the title
+    footnote_placement: FootnotePlacement,
+    math_timeout: Duration,     // per latex/dvisvgm invocation
+    containers: BTreeMap<String, ContainerSpec>,  // `:::name` => wrapper
+    code_tabs: bool,            // adjacent labeled code blocks become tabs (`:::tabs` always does)
+    slug_from_title: bool,      // name output files after the title instead of the source file
+    emit_sidecar: bool,         // name.json with the post's metadata next to name.html
+    back_to_top: bool,          // floating link back to an anchor at the top of the content
+    syntax_highlighting: bool,  // color code blocks; needs the `highlight` feature
+    autodetect_code_lang: bool, // guess a language for fences without one
+    template_paths: Vec<PathBuf>,  // a post is stale if any of these is newer than its output
+    template_sources: TemplateSources,
+    force: bool,                // rebuild posts whose output looks up to date
+    demote_extra_h1s: bool,     // render every h1 after the first as h2
+    math_cache_scope: MathCacheScope,
+    on_post_error: PostErrorPolicy,
+    display_math_align: DisplayMathAlign,
+    empty_list_items: EmptyListItemPolicy,
+}
+
+
+// the built-in layout and templates, all options off; reads nothing from disk
+impl Default for CompilerConfig {
+    fn default() -> Self {
+        CompilerConfig {
+            posts_dir: Path::new("posts/").to_path_buf(),
+            images_url: "/static/images".to_string(),
+            images_dir: Path::new("www/static/images").to_path_buf(),
+            output_dir: Path::new("www/posts").to_path_buf(),
+            cache_dir: Path::new(".minissg-cache").to_path_buf(),
+            templates: Templates {
+                post: DEFAULT_POST_TEMPLATE.to_string(),
+                index: None,
+                tag: None,
+                rss: DEFAULT_RSS_TEMPLATE.to_string(),
+                atom: DEFAULT_ATOM_TEMPLATE.to_string(),
+                sitemap: DEFAULT_SITEMAP_TEMPLATE.to_string(),
+            },
+            math_template: DEFAULT_MATH_TEMPLATE.to_string(),
+            math_font_package: None,
+            optimize_svg: false,
+            collapsible_sections: false,
+            max_list_depth: 8,
+            code_copy_button: false,
+            math_backend: MathBackend::Svg,
+            merge_subtitle: false,
+            embed_source: false,
+            code_wrap: CodeWrap::Scroll,
+            alt_text_policy: AltTextPolicy::Warn,
+            bibliography: BTreeMap::new(),
+            number_equations: false,
+            number_figures: false,
+            output_format: OutputFormat::Html,
+            posts_url: "/posts".to_string(),
+            site_url: String::new(),
+            site_title: "minissg".to_string(),
+            nav_links: Vec::new(),
+            code_lang_aliases: BTreeMap::new(),
+            lead_paragraph: false,
+            show_code_lang: false,
+            math_noscript_fallback: false,
+            footnote_id_prefix: true,
+            footnote_backref_symbol: "↩".to_string(),
+            footnote_ref_separator: ",".to_string(),
+            image_thumbnails: None,
+            preserve_spaces: false,
+            math_svg_class: None,
+            strict_front_matter: false,
+            edit_url_template: None,
+            math_error_display: MathErrorDisplay::Code,
+            excerpt_words: 50,
+            indent_width: 4,
+            title_from_h1: false,
+            footnote_placement: FootnotePlacement::InPlace,
+            math_timeout: Duration::from_secs(30),
+            containers: BTreeMap::new(),
+            code_tabs: false,
+            slug_from_title: false,
+            emit_sidecar: false,
+            back_to_top: false,
+            syntax_highlighting: false,
+            autodetect_code_lang: false,
+            empty_list_items: EmptyListItemPolicy::Skip,
+            template_paths: Vec::new(),
+            template_sources: TemplateSources::default(),
+            force: false,
+            demote_extra_h1s: false,
+            math_cache_scope: MathCacheScope::Global,
+            on_post_error: PostErrorPolicy::Skip,
+            display_math_align: DisplayMathAlign::Center,
+        }
+    }
+}
+
+/// minissg.toml at `config_path` (the defaults if it's missing) plus the templates and
+/// bibliography; `posts_dir` and `output_dir` override the file, `force` rebuilds up to date
+/// posts and `no_math` shows formulas as source
+pub fn load_config(config_path: &Path, posts_dir: Option<PathBuf>, output_dir: Option<PathBuf>,
+                   force: bool, no_math: bool) -> Result<CompilerConfig, MinissgError> {
+    let file_cfg = load_config_file(config_path)?;
+    let defaults = CompilerConfig::default();
+    let posts_dir = posts_dir.or(file_cfg.posts_dir).unwrap_or(defaults.posts_dir);
+    let images_url = file_cfg.images_url.unwrap_or(defaults.images_url);
+    let images_dir = file_cfg.images_dir.unwrap_or(defaults.images_dir);
+    let output_dir = output_dir.or(file_cfg.output_dir).unwrap_or(defaults.output_dir);
+    let cache_dir = file_cfg.cache_dir.unwrap_or(defaults.cache_dir);
+    // a configured template has to exist; the usual one may be missing, then the built-in is used
+    let post_template_path = file_cfg.post_template.clone().unwrap_or_else(|| Path::new("templates/template.html").to_path_buf());
+    let math_template_path = file_cfg.math_template.clone().unwrap_or_else(|| Path::new("templates/math.tex").to_path_buf());
+    let template_sources = TemplateSources {
+        index: file_cfg.index_template,
+        tag: file_cfg.tag_template,
+        rss: file_cfg.rss_template,
+        atom: file_cfg.atom_template,
+        sitemap: file_cfg.sitemap_template,
+    };
+    let templates = Templates {
+        post: read_template(file_cfg.post_template)?
+            .or_else(|| std::fs::read_to_string(&post_template_path).ok())
+            .unwrap_or(defaults.templates.post),
+        index: read_template(template_sources.index.clone())?,
+        tag: read_template(template_sources.tag.clone())?,
+        rss: read_template(template_sources.rss.clone())?.unwrap_or(defaults.templates.rss),
+        atom: read_template(template_sources.atom.clone())?.unwrap_or(defaults.templates.atom),
+        sitemap: read_template(template_sources.sitemap.clone())?.unwrap_or(defaults.templates.sitemap),
+    };
+    let math_template = read_template(file_cfg.math_template)?
+        .or_else(|| std::fs::read_to_string(&math_template_path).ok())
+        .unwrap_or(defaults.math_template);
+    check_math_template(&math_template, &math_template_path)?;
+    let containers = file_cfg.containers.unwrap_or(defaults.containers);
+    check_containers(&containers, config_path)?;
+    let bibliography = load_bibliography(&file_cfg.bibliography.unwrap_or_else(|| Path::new("bibliography.yaml").to_path_buf()));
+
+    Ok(CompilerConfig {
+        posts_dir,
+        images_url,
+        images_dir,
+        output_dir,
+        cache_dir,
+        templates,
+        math_template,
+        math_font_package: file_cfg.math_font_package.or(defaults.math_font_package),
+        optimize_svg: file_cfg.optimize_svg.unwrap_or(defaults.optimize_svg),
+        collapsible_sections: file_cfg.collapsible_sections.unwrap_or(defaults.collapsible_sections),
+        max_list_depth: file_cfg.max_list_depth.unwrap_or(defaults.max_list_depth),
+        code_copy_button: file_cfg.code_copy_button.unwrap_or(defaults.code_copy_button),
+        math_backend: if no_math { MathBackend::Off } else { file_cfg.math_backend.unwrap_or(defaults.math_backend) },
+        merge_subtitle: file_cfg.merge_subtitle.unwrap_or(defaults.merge_subtitle),
+        embed_source: file_cfg.embed_source.unwrap_or(defaults.embed_source),
+        code_wrap: file_cfg.code_wrap.unwrap_or(defaults.code_wrap),
+        alt_text_policy: file_cfg.alt_text_policy.unwrap_or(defaults.alt_text_policy),
+        bibliography,
+        number_equations: file_cfg.number_equations.unwrap_or(defaults.number_equations),
+        number_figures: file_cfg.number_figures.unwrap_or(defaults.number_figures),
+        output_format: file_cfg.output_format.unwrap_or(defaults.output_format),
+        posts_url: file_cfg.posts_url.unwrap_or(defaults.posts_url),
+        site_url: file_cfg.site_url.unwrap_or(defaults.site_url),
+        site_title: file_cfg.site_title.unwrap_or(defaults.site_title),
+        nav_links: file_cfg.nav_links.unwrap_or(defaults.nav_links),
+        code_lang_aliases: file_cfg.code_lang_aliases.unwrap_or(defaults.code_lang_aliases),
+        lead_paragraph: file_cfg.lead_paragraph.unwrap_or(defaults.lead_paragraph),
+        show_code_lang: file_cfg.show_code_lang.unwrap_or(defaults.show_code_lang),
+        math_noscript_fallback: file_cfg.math_noscript_fallback.unwrap_or(defaults.math_noscript_fallback),
+        footnote_id_prefix: file_cfg.footnote_id_prefix.unwrap_or(defaults.footnote_id_prefix),
+        footnote_backref_symbol: file_cfg.footnote_backref_symbol.unwrap_or(defaults.footnote_backref_symbol),
+        footnote_ref_separator: file_cfg.footnote_ref_separator.unwrap_or(defaults.footnote_ref_separator),
+        image_thumbnails: file_cfg.image_thumbnails.or(defaults.image_thumbnails),
+        preserve_spaces: file_cfg.preserve_spaces.unwrap_or(defaults.preserve_spaces),
+        math_svg_class: file_cfg.math_svg_class.or(defaults.math_svg_class),
+        strict_front_matter: file_cfg.strict_front_matter.unwrap_or(defaults.strict_front_matter),
+        edit_url_template: file_cfg.edit_url_template.or(defaults.edit_url_template),
+        math_error_display: file_cfg.math_error_display.unwrap_or(defaults.math_error_display),
+        excerpt_words: file_cfg.excerpt_words.unwrap_or(defaults.excerpt_words),
+        indent_width: file_cfg.indent_width.unwrap_or(defaults.indent_width),
+        title_from_h1: file_cfg.title_from_h1.unwrap_or(defaults.title_from_h1),
+        footnote_placement: file_cfg.footnote_placement.unwrap_or(defaults.footnote_placement),
+        math_timeout: file_cfg.math_timeout.map(Duration::from_secs).unwrap_or(defaults.math_timeout),
+        containers,
+        code_tabs: file_cfg.code_tabs.unwrap_or(defaults.code_tabs),
+        slug_from_title: file_cfg.slug_from_title.unwrap_or(defaults.slug_from_title),
+        emit_sidecar: file_cfg.emit_sidecar.unwrap_or(defaults.emit_sidecar),
+        back_to_top: file_cfg.back_to_top.unwrap_or(defaults.back_to_top),
+        syntax_highlighting: file_cfg.syntax_highlighting.unwrap_or(defaults.syntax_highlighting),
+        autodetect_code_lang: file_cfg.autodetect_code_lang.unwrap_or(defaults.autodetect_code_lang),
+        empty_list_items: file_cfg.empty_list_items.unwrap_or(defaults.empty_list_items),
+        template_paths: vec![post_template_path, math_template_path],
+        template_sources,
+        force,
+        demote_extra_h1s: file_cfg.demote_extra_h1s.unwrap_or(defaults.demote_extra_h1s),
+        math_cache_scope: file_cfg.math_cache_scope.unwrap_or(defaults.math_cache_scope),
+        on_post_error: file_cfg.on_post_error.unwrap_or(defaults.on_post_error),
+        display_math_align: file_cfg.display_math_align.unwrap_or(defaults.display_math_align),
+    })
+}
+
+// a template the config names has to exist
+fn read_template(path: Option<PathBuf>) -> Result<Option<String>, MinissgError> {
+    let Some(path) = path else { return Ok(None) };
+    std::fs::read_to_string(&path)
+        .map(Some)
+        .map_err(|source| MinissgError::Io{path, source})
+}
+
+// without the placeholder every equation would silently vanish from the .tex
+fn check_math_template(template: &str, path: &Path) -> Result<(), MinissgError> {
+    if !template.is_empty() && !template.contains("{{content}}") {
+        return Err(MinissgError::Config{
+            path: path.to_path_buf(),
+            message: "no {{content}} placeholder".to_string(),
+        });
+    }
+    Ok(())
+}
+
+// the tag is pasted into the html as is, so it has to be a plain element name
+fn check_containers(containers: &BTreeMap<String, ContainerSpec>, path: &Path) -> Result<(), MinissgError> {
+    for (name, spec) in containers {
+        let mut chars = spec.tag.chars();
+        if !chars.next().is_some_and(|c| c.is_ascii_alphabetic()) || !chars.all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return Err(MinissgError::Config{
+                path: path.to_path_buf(),
+                message: format!("container '{}' has an invalid tag '{}'", name, spec.tag),
+            });
+        }
+    }
+    Ok(())
+}
+
+// a missing file means the defaults; a broken one is an error rather than silently ignored
+fn load_config_file(path: &Path) -> Result<ConfigFile, MinissgError> {
+    let Ok(src) = std::fs::read_to_string(path) else { return Ok(ConfigFile::default()) };
+    toml::from_str(&src).map_err(|e| MinissgError::Config{path: path.to_path_buf(), message: e.to_string()})
+}
+
+// `key: reference text` per line; a missing file just means no citations
+fn load_bibliography(path: &Path) -> BTreeMap<String, String> {
+    let Ok(src) = std::fs::read_to_string(path) else { return BTreeMap::new() };
+    match parse_yaml_front_matter(&src) {
+        Ok(table) => table.into_iter()
+            .filter_map(|(k, v)| v.as_str().map(|v| (k, v.to_string())))
+            .collect(),
+        Err(e) => {
+            warn(&format!("invalid bibliography {}: {}", path.display(), e));
+            BTreeMap::new()
+        }
+    }
+}
+
+/* ========================================
+                  compiling 
+   ======================================== */
+fn post_paths(cfg: &CompilerConfig) -> Vec<PathBuf> {
+    let entries = match std::fs::read_dir(&cfg.posts_dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            error(&format!("could not read {}: {}", cfg.posts_dir.display(), e));
+            return Vec::new();
+        }
+    };
+    let mut paths = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().and_then(|s| s.to_str()) == Some("md"))
+        .collect::<Vec<_>>();
+    paths.sort();
+    paths
+}
+
+/// compiles every post, then the index, feeds, tag pages, search index and sitemap.
+/// Err only with `on_post_error = "abort"`, for the first post that failed
+pub fn compile_all(cfg: &CompilerConfig) -> Result<(), MinissgError> {
+    let mut jobs = Vec::new();
+    let mut claimed: BTreeMap<String, PathBuf> = BTreeMap::new();
+    for path in post_paths(cfg) {
+        let slug = post_slug(&path, cfg);
+        if let Some(other) = claimed.get(&slug) {
+            warn(&format!("{} and {} both write {}.html", other.display(), path.display(), slug));
+        }
+        let output_path = cfg.output_dir.join(&slug).with_extension("html");
+        jobs.push((path.clone(), output_path));
+        claimed.insert(slug, path);
+    }
+
+    // posts are independent; each log line is written whole, so logs interleave by line at worst
+    let posts = jobs.par_iter()
+        .filter_map(|(path, output_path)| match compile_post(path, output_path, cfg) {
+            Ok(meta) => Some(Ok(meta)),
+            Err(e) => post_failed(e, cfg).err().map(Err),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    write_search_index(&posts, cfg);
+    let all = posts.iter().collect::<Vec<_>>();
+    write_listing(&cfg.output_dir.join("index.html"), cfg.templates.index.as_deref(), &cfg.site_title, &all, cfg);
+    write_feed(&cfg.output_dir.join("rss.xml"), &cfg.site_title, &all, cfg);
+    write_atom_feed(&cfg.output_dir.join("atom.xml"), &cfg.site_title, &all, cfg);
+    write_tag_pages(&posts, cfg);
+    write_sitemap(&posts, cfg);
+    Ok(())
+}
+
+/// compiles one post, reporting a failure like [`compile_all`] does
+pub fn compile_file(path: &Path, cfg: &CompilerConfig) -> Result<(), MinissgError> {
+    match compile_post(path, &post_output_path(path, cfg), cfg) {
+        Ok(_) => Ok(()),
+        Err(e) => post_failed(e, cfg),
+    }
+}
+
+fn post_output_path(path: &Path, cfg: &CompilerConfig) -> PathBuf {
+    cfg.output_dir.join(post_slug(path, cfg)).with_extension("html")
+}
+
+/// rebuilds posts as they're saved, and everything when a template changes. Blocks until the
+/// watcher goes away; compile errors are reported like in a normal build and watching goes on.
+/// `on_rebuild` runs after every batch of recompiles
+pub fn watch_posts(mut cfg: CompilerConfig, on_rebuild: impl Fn()) -> Result<(), MinissgError> {
+    // a broken post shouldn't end the session
+    cfg.on_post_error = PostErrorPolicy::Skip;
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)
+        .map_err(|e| MinissgError::Watch(e.to_string()))?;
+    // editors often save by replacing the file, so watch the directories rather than the files
+    let sources = &cfg.template_sources;
+    let mut dirs = vec![cfg.posts_dir.clone()];
+    dirs.extend(cfg.template_paths.iter()
+        .chain([&sources.index, &sources.tag, &sources.rss, &sources.atom, &sources.sitemap].into_iter().flatten())
+        .filter_map(|p| p.parent())
+        .map(Path::to_path_buf));
+    dirs.sort();
+    dirs.dedup();
+    for dir in &dirs {
+        let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir.as_path() };
+        if let Err(e) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+            warn(&format!("could not watch {}: {}", dir.display(), e));
+        }
+    }
+
+    info!("compiling all posts...");
+    compile_all(&cfg)?;
+    // everything is built now; from here on only what changed is recompiled
+    cfg.force = false;
+    info!("watching {} for changes...", cfg.posts_dir.display());
+    while let Ok(first) = rx.recv() {
+        // one save tends to arrive as several events; wait for a quiet moment
+        let mut changed = BTreeSet::new();
+        let mut event = Some(first);
+        while let Some(e) = event {
+            if let Ok(e) = e
+                && !e.kind.is_access() {
+                changed.extend(e.paths);
+            }
+            event = rx.recv_timeout(Duration::from_millis(200)).ok();
+        }
+
+        let reloaded = reload_templates(&changed, &mut cfg);
+        // a post shows up in the index, feeds, tag pages, search index and sitemap too, so
+        // those are rebuilt with it; up-to-date posts are skipped
+        let post_changed = changed.iter().any(|path| path.extension().and_then(|s| s.to_str()) == Some("md"));
+        if reloaded || post_changed {
+            info!("compiling all posts...");
+            compile_all(&cfg)?;
+            on_rebuild();
+        }
+    }
+    Ok(())
+}
+
+// re-reads the changed templates (notify reports canonical paths); one that can't be read or
+// no longer validates is reported and the old one kept. true if any was replaced
+fn reload_templates(changed: &BTreeSet<PathBuf>, cfg: &mut CompilerConfig) -> bool {
+    let reload = |path: Option<&PathBuf>| {
+        let path = path.filter(|path| std::fs::canonicalize(path).is_ok_and(|path| changed.contains(&path)))?;
+        info!("template changed: {}", path.display());
+        std::fs::read_to_string(path)
+            .map_err(|e| error(&format!("could not read {}, keeping the old template: {}", path.display(), e)))
+            .ok()
+    };
+    let mut reloaded = false;
+    // post template first, then the math one
+    if let Some(template) = reload(cfg.template_paths.first()) {
+        cfg.templates.post = template;
+        reloaded = true;
+    }
+    if let Some(template) = reload(cfg.template_paths.get(1)) {
+        match check_math_template(&template, &cfg.template_paths[1]) {
+            Ok(()) => {
+                cfg.math_template = template;
+                reloaded = true;
+            }
+            Err(e) => error(&format!("{}, keeping the old template", e)),
+        }
+    }
+    if let Some(template) = reload(cfg.template_sources.index.as_ref()) {
+        cfg.templates.index = Some(template);
+        reloaded = true;
+    }
+    if let Some(template) = reload(cfg.template_sources.tag.as_ref()) {
+        cfg.templates.tag = Some(template);
+        reloaded = true;
+    }
+    if let Some(template) = reload(cfg.template_sources.rss.as_ref()) {
+        cfg.templates.rss = template;
+        reloaded = true;
+    }
+    if let Some(template) = reload(cfg.template_sources.atom.as_ref()) {
+        cfg.templates.atom = template;
+        reloaded = true;
+    }
+    if let Some(template) = reload(cfg.template_sources.sitemap.as_ref()) {
+        cfg.templates.sitemap = template;
+        reloaded = true;
+    }
+    reloaded
+}
+
+// the directory output urls are relative to: output_dir minus its posts_url suffix
+#[cfg(feature = "serve")]
+fn site_root(cfg: &CompilerConfig) -> PathBuf {
+    let suffix = Path::new(cfg.posts_url.trim_start_matches('/'));
+    let mut root = cfg.output_dir.clone();
+    if !suffix.as_os_str().is_empty() && root.ends_with(suffix) {
+        for _ in suffix.components() {
+            root.pop();
+        }
+    }
+    if root.as_os_str().is_empty() { PathBuf::from(".") } else { root }
+}
+
+// pages reconnect on their own if the server restarts
+#[cfg(feature = "serve")]
+const LIVE_RELOAD_SCRIPT: &str =
+    "<script>new EventSource(\"/__livereload\").onmessage = () => location.reload();</script>";
+
+#[cfg(feature = "serve")]
+const SERVE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+#[cfg(feature = "serve")]
+const LIVE_RELOAD_HEARTBEAT: std::time::Duration = std::time::Duration::from_secs(15);
+
+/// [`watch_posts`], plus a tiny file server for the site on 127.0.0.1:`port`. Served pages
+/// reload themselves after each rebuild through the `/__livereload` event stream
+#[cfg(feature = "serve")]
+pub fn serve(cfg: CompilerConfig, port: u16) -> Result<(), MinissgError> {
+    use std::net::TcpListener;
+    use std::sync::Condvar;
+
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .map_err(|source| MinissgError::Serve{port, source})?;
+    let root = site_root(&cfg);
+    info!("serving {} at http://localhost:{}/", root.display(), port);
+
+    let rebuilds = Arc::new((Mutex::new(0usize), Condvar::new()));
+    let signal = rebuilds.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let (root, rebuilds) = (root.clone(), rebuilds.clone());
+            std::thread::spawn(move || serve_request(stream, &root, &rebuilds));
+        }
+    });
+
+    watch_posts(cfg, move || {
+        let (count, changed) = &*signal;
+        *count.lock().unwrap() += 1;
+        changed.notify_all();
+    })
+}
+
+// the file a request path names, or None when it's missing or resolves outside root (`..`, symlinks)
+#[cfg(feature = "serve")]
+fn resolve_request_path(root: &Path, path: &str) -> Option<PathBuf> {
+    let root = root.canonicalize().ok()?;
+    let mut file = root.join(path.trim_start_matches('/')).canonicalize().ok()?;
+    if file.is_dir() {
+        file.push("index.html");
+    }
+    (file.starts_with(&root) && file.is_file()).then_some(file)
+}
+
+#[cfg(feature = "serve")]
+fn serve_request(mut stream: std::net::TcpStream, root: &Path,
+                 rebuilds: &(Mutex<usize>, std::sync::Condvar)) {
+    use std::io::{BufRead, BufReader, Write};
+
+    // a client that stops reading or writing gives up its thread instead of holding it forever
+    let _ = stream.set_read_timeout(Some(SERVE_TIMEOUT));
+    let _ = stream.set_write_timeout(Some(SERVE_TIMEOUT));
+    let mut request_line = String::new();
+    let mut reader = BufReader::new(&stream);
+    if reader.read_line(&mut request_line).is_err() {
+        return;
+    }
+    // headers are read and ignored
+    let mut header = String::new();
+    while reader.read_line(&mut header).is_ok_and(|n| n > 0) && header.trim() != "" {
+        header.clear();
+    }
+    let path = request_line.split_whitespace().nth(1).unwrap_or("/");
+    let path = path.split(['?', '#']).next().unwrap_or("/");
+
+    if path == "/__livereload" {
+        let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\n\r\n");
+        let (count, changed) = rebuilds;
+        let mut seen = *count.lock().unwrap();
+        loop {
+            let (current, waited) = changed
+                .wait_timeout_while(count.lock().unwrap(), LIVE_RELOAD_HEARTBEAT, |c| *c == seen)
+                .unwrap();
+            seen = *current;
+            drop(current);
+            // the heartbeat is an sse comment; writing it is how a closed tab gets noticed
+            let event: &[u8] = if waited.timed_out() { b": ping\n\n" } else { b"data: reload\n\n" };
+            if stream.write_all(event).is_err() {
+                return;
+            }
+        }
+    }
+
+    let (status, file) = match resolve_request_path(root, path) {
+        Some(file) => ("200 OK", file),
+        None => ("404 Not Found", root.join("404.html")),
+    };
+    let body = std::fs::read(&file).unwrap_or_default();
+    let content_type = match file.extension().and_then(|s| s.to_str()) {
+        Some("html") => "text/html; charset=utf-8",
+        Some("css") => "text/css",
+        Some("js") => "text/javascript",
+        Some("json") => "application/json",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("xml") => "application/xml",
+        _ => "application/octet-stream",
+    };
+    let body = if content_type.starts_with("text/html") {
+        let html = String::from_utf8_lossy(&body);
+        match html.rfind("</body>") {
+            Some(i) => format!("{}{}{}", &html[..i], LIVE_RELOAD_SCRIPT, &html[i..]),
+            None => format!("{}{}", html, LIVE_RELOAD_SCRIPT),
+        }.into_bytes()
+    } else {
+        body
+    };
+    let head = format!("HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status, content_type, body.len());
+    let _ = stream.write_all(head.as_bytes()).and_then(|_| stream.write_all(&body));
+}
+
+/// every post in one templated page at `out_path`; ids are namespaced per post so they can't collide
+pub fn export_single_page(out_path: &Path, cfg: &CompilerConfig) {
+    let mut content = String::new();
+    for path in post_paths(cfg) {
+        let Ok(file) = std::fs::read_to_string(&path) else {
+            error(&format!("invalid file path: {}", path.display()));
+            continue;
+        };
+        info!("compiling: {}", path.display());
+        let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("untitled");
+        let slug = post_slug(&path, cfg);
+        let (front, body) = split_front_matter(&file);
+        let parsed = parse(body, cfg);
+        let title = post_title(&front, &parsed, stem, cfg);
+        let post_content = render_post_body(parsed, &front, &format!("{}-", slug), &slug, cfg);
+        content.push_str(&format!(
+            "<article id=\"{}\">\n<h1><a href=\"#{}\">{}</a></h1>\n<hr><br>{}</article>\n",
+            escape_html(&slug), escape_html(&slug), escape_html(&title), post_content
+        ));
+    }
+    let html = fill_template(&cfg.templates.post, &[
+        ("content", &content),
+        ("title", "all posts"),
+        ("nav", &render_nav("", cfg)),
+        ("edit_url", ""),
+        ("excerpt", ""),
+    ]);
+    write_output(out_path, &html);
+}
+
+// resolved metadata next to the post, for deploy tooling
+fn write_sidecar(meta: &PostMeta, path: &Path) {
+    let sidecar = serde_json::json!({
+        "title": meta.title,
+        "url": meta.url,
+        "date": meta.date,
+        "tags": meta.tags,
+        "draft": meta.draft,
+        "word_count": meta.text.split_whitespace().count(),
+        "excerpt": meta.excerpt,
+    });
+    write_output(path, &serde_json::to_string_pretty(&sidecar).unwrap());
+}
+
+// client-side search: title, url, tags and plain-text body of every published post
+fn write_search_index(posts: &[PostMeta], cfg: &CompilerConfig) {
+    let entries = posts.iter()
+        .filter(|post| !post.draft)
+        .map(|post| serde_json::json!({
+            "title": post.title,
+            "url": post.url,
+            "tags": post.tags,
+            "body": post.text,
+        }))
+        .collect::<Vec<_>>();
+    let path = cfg.output_dir.join("search.json");
+    info!("writing search index: {}", path.display());
+    write_output(&path, &serde_json::Value::Array(entries).to_string());
+}
+
+// every generated page: the index, the tag pages and each published post.
+// <loc> is site_url + the page's url, a listing's <lastmod> its newest post's
+fn write_sitemap(posts: &[PostMeta], cfg: &CompilerConfig) {
+    let published = posts.iter().filter(|post| !post.draft).collect::<Vec<_>>();
+    let newest = |posts: &[&PostMeta]| posts.iter().filter_map(|post| post.modified).max();
+    let base = cfg.posts_url.trim_end_matches('/');
+    let mut pages = vec![(format!("{}/index.html", base), newest(&published))];
+    for tag in published_tags(posts) {
+        let tagged = published.iter().copied().filter(|post| post.tags.contains(tag)).collect::<Vec<_>>();
+        pages.push((format!("{}/tags/{}.html", base, slugify(tag)), newest(&tagged)));
+    }
+    pages.extend(published.iter().map(|post| (post.url.clone(), post.modified)));
+
+    let mut urls = String::new();
+    for (url, modified) in pages {
+        urls.push_str(&format!("<url>\n<loc>{}{}</loc>\n",
+            escape_html(cfg.site_url.trim_end_matches('/')), escape_html(&url)));
+        if let Some(modified) = modified.and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok()) {
+            let (y, m, d) = civil_from_days(modified.as_secs() as i64 / 86400);
+            urls.push_str(&format!("<lastmod>{:04}-{:02}-{:02}</lastmod>\n", y, m, d));
+        }
+        urls.push_str("</url>\n");
+    }
+    let sitemap = fill_template(&cfg.templates.sitemap, &[("urls", &urls)]);
+    let path = cfg.output_dir.join("sitemap.xml");
+    info!("writing sitemap: {}", path.display());
+    write_output(&path, &sitemap);
+}
+
+// tags/<tag>.html and tags/<tag>.xml: a page and a feed holding just the posts tagged with it
+fn write_tag_pages(posts: &[PostMeta], cfg: &CompilerConfig) {
+    for tag in published_tags(posts) {
+        let tagged = posts.iter().filter(|post| post.tags.contains(tag)).collect::<Vec<_>>();
+        let path = cfg.output_dir.join("tags").join(slugify(tag));
+        let title = format!("{}: {}", cfg.site_title, tag);
+        write_listing(&path.with_extension("html"), cfg.templates.tag.as_deref(), &title, &tagged, cfg);
+        write_feed(&path.with_extension("xml"), &title, &tagged, cfg);
+    }
+}
+
+// every tag on a published post, each once
+fn published_tags(posts: &[PostMeta]) -> BTreeSet<&String> {
+    posts.iter()
+        .filter(|post| !post.draft)
+        .flat_map(|post| &post.tags)
+        .collect()
+}
+
+// {{key}} => value in one pass, so a value that itself contains {{key}} is left alone;
+// unknown keys are kept as written
+fn fill_template(template: &str, vars: &[(&str, &str)]) -> String {
+    PLACEHOLDER_REGEX.replace_all(template, |caps: &regex::Captures| {
+        match vars.iter().find(|(key, _)| *key == &caps[1]) {
+            Some((_, value)) => value.to_string(),
+            None => caps[0].to_string(),
+        }
+    }).to_string()
+}
+
+// what every feed and list shows: no drafts, newest first
+fn published_newest_first<'a>(posts: &[&'a PostMeta]) -> Vec<&'a PostMeta> {
+    let mut posts = posts.iter().copied().filter(|post| !post.draft).collect::<Vec<_>>();
+    posts.sort_by_key(|post| std::cmp::Reverse(post_timestamp(post)));
+    posts
+}
+
+// a page linking to each post; `template` falls back to the post template
+fn write_listing(path: &Path, template: Option<&str>, title: &str, posts: &[&PostMeta], cfg: &CompilerConfig) {
+    let mut list = String::from("<ul class=\"post-list\">\n");
+    for post in published_newest_first(posts) {
+        list.push_str(&format!("<li><a href=\"{}\">{}</a>", escape_url(&post.url), escape_html(&post.title)));
+        if let Some(date) = &post.date {
+            list.push_str(&format!(" <span class=\"post-date\">{}</span>", escape_html(date)));
+        }
+        list.push_str("</li>\n");
+    }
+    list.push_str("</ul>\n");
+    let page = fill_template(template.unwrap_or(&cfg.templates.post), &[
+        ("content", &list),
+        ("title", &escape_html(title)),
+        ("nav", &render_nav("", cfg)),
+        ("edit_url", ""),
+        ("excerpt", ""),
+    ]);
+    info!("writing listing: {}", path.display());
+    write_output(path, &page);
+}
+
+// rss 2.0
+fn write_feed(path: &Path, title: &str, posts: &[&PostMeta], cfg: &CompilerConfig) {
+    let items = published_newest_first(posts).into_iter()
+        .map(|post| rss_item(post, cfg))
+        .collect::<String>();
+    let link = format!("{}/", cfg.site_url.trim_end_matches('/'));
+    let feed = fill_template(&cfg.templates.rss, &[
+        ("title", &escape_html(title)),
+        ("link", &escape_html(&link)),
+        ("items", &items),
+    ]);
+    info!("writing feed: {}", path.display());
+    write_output(path, &feed);
+}
+
+// atom 1.0, same posts as the rss feed
+fn write_atom_feed(path: &Path, title: &str, posts: &[&PostMeta], cfg: &CompilerConfig) {
+    let posts = published_newest_first(posts);
+    let base = cfg.site_url.trim_end_matches('/');
+    let mut entries = String::new();
+    for post in &posts {
+        let link = escape_html(&format!("{}{}", base, post.url));
+        entries.push_str(&format!(
+            "<entry>\n<title>{}</title>\n<link href=\"{}\"/>\n<id>{}</id>\n<summary>{}</summary>\n",
+            escape_html(&post.title), link, link, escape_html(&post.summary)
+        ));
+        if let Some(timestamp) = post_timestamp(post) {
+            entries.push_str(&format!("<updated>{}</updated>\n", rfc3339_date(timestamp)));
+        }
+        entries.push_str("</entry>\n");
+    }
+    let updated = posts.first().and_then(|post| post_timestamp(post)).unwrap_or(0);
+    let feed = fill_template(&cfg.templates.atom, &[
+        ("title", &escape_html(title)),
+        ("link", &escape_html(&format!("{}/", base))),
+        ("updated", &rfc3339_date(updated)),
+        ("entries", &entries),
+    ]);
+    info!("writing feed: {}", path.display());
+    write_output(path, &feed);
+}
+
+fn rss_item(post: &PostMeta, cfg: &CompilerConfig) -> String {
+    let link = escape_html(&format!("{}{}", cfg.site_url.trim_end_matches('/'), post.url));
+    let mut item = format!(
+        "<item>\n<title>{}</title>\n<link>{}</link>\n<guid>{}</guid>\n<description>{}</description>\n",
+        escape_html(&post.title), link, link, escape_html(&post.summary)
+    );
+    if let Some(timestamp) = post_timestamp(post) {
+        item.push_str(&format!("<pubDate>{}</pubDate>\n", rfc822_date(timestamp)));
+    }
+    for tag in &post.tags {
+        item.push_str(&format!("<category>{}</category>\n", escape_html(tag)));
+    }
+    item.push_str("</item>\n");
+    item
+}
+
+// seconds since the epoch: the front matter date, else the source's mtime
+fn post_timestamp(post: &PostMeta) -> Option<i64> {
+    post.date.as_deref().and_then(parse_date).map(|days| days * 86400)
+        .or_else(|| post.modified
+            .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs() as i64))
+}
+
+// 2024-01-15 (anything after the day is ignored) => days since 1970-01-01
+fn parse_date(date: &str) -> Option<i64> {
+    let mut parts = date.get(..10)?.split('-').map(|p| p.parse::<i64>().ok());
+    let (y, m, d) = (parts.next()??, parts.next()??, parts.next()??);
+    if !(1..=12).contains(&m) || !(1..=31).contains(&d) {
+        return None;
+    }
+    // Howard Hinnant's days_from_civil
+    let (y, m) = if m <= 2 { (y - 1, m + 9) } else { (y, m - 3) };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * m + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    Some(era * 146097 + doe - 719468)
+}
+
+// seconds since the epoch => Mon, 15 Jan 2024 00:00:00 +0000
+fn rfc822_date(timestamp: i64) -> String {
+    let (days, secs) = (timestamp.div_euclid(86400), timestamp.rem_euclid(86400));
+    let (y, m, d) = civil_from_days(days);
+    const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+    format!("{}, {:02} {} {} {:02}:{:02}:{:02} +0000",
+        WEEKDAYS[days.rem_euclid(7) as usize], d, MONTHS[m as usize - 1], y,
+        secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+// seconds since the epoch => 2024-01-15T00:00:00Z
+fn rfc3339_date(timestamp: i64) -> String {
+    let (days, secs) = (timestamp.div_euclid(86400), timestamp.rem_euclid(86400));
+    let (y, m, d) = civil_from_days(days);
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", y, m, d, secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+// days since 1970-01-01 => (year, month, day); the inverse of parse_date
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    (yoe + era * 400 + i64::from(m <= 2), m, d)
+}
+
+fn compile_post(in_path: &Path,
+                out_path: &Path,
+                cfg: &CompilerConfig,
+) -> Result<PostMeta, MinissgError> {
+    info!("compiling: {} => {}", in_path.display(), out_path.display());
+
+    // read file
+    let file = std::fs::read_to_string(in_path)
+        .map_err(|source| MinissgError::Io{path: in_path.to_path_buf(), source})?;
+    let (front, body) = split_front_matter(&file);
+
+    // parse
+    let mut parsed = parse(body, cfg);
+    let stem = in_path.file_stem().and_then(|s| s.to_str()).unwrap_or("untitled");
+    let slug = out_path.file_stem().and_then(|s| s.to_str()).unwrap_or(stem).to_string();
+    let title = post_title(&front, &parsed, stem, cfg);
+    lint_multiple_h1s(&mut parsed, cfg);
+    lint_header_levels(&parsed);
+    if cfg.strict_front_matter {
+        lint_front_matter(&front, cfg);
+    }
+    if has_math(&parsed) {
+        check_math_tooling(cfg);
+    }
+
+    // plain text goes to name.txt, and listings and feeds have to link there
+    let final_path = if cfg.output_format == OutputFormat::PlainText {
+        out_path.with_extension("txt")
+    } else {
+        out_path.to_path_buf()
+    };
+    let meta = PostMeta {
+        title: title.clone(),
+        url: post_url(&final_path, cfg),
+        date: front.extra.get("date").map(|d| d.as_str().map(str::to_string).unwrap_or_else(|| d.to_string())),
+        tags: front.tags.clone(),
+        draft: front.draft,
+        text: render_plain_text(&parsed),
+        excerpt: excerpt(&parsed, cfg.excerpt_words, &slug, cfg),
+        summary: parsed.iter()
+            .find(|b| matches!(b, Block::Paragraph(_)))
+            .map(Block::to_plain_text)
+            .unwrap_or_default(),
+        modified: std::fs::metadata(in_path).and_then(|m| m.modified()).ok(),
+    };
+
+    // unchanged source + template + settings + images => reuse the last clean render
+    let cache_path = cfg.cache_dir
+        .join("posts")
+        .join(render_cache_key(&file, &title, in_path, &meta.url, &parsed, cfg))
+        .with_extension("html");
+    // a degraded render isn't cached, so its output isn't trusted either and its warnings come back
+    let clean = cfg.output_format == OutputFormat::PlainText || cache_path.exists();
+    if !cfg.force && clean && is_up_to_date(in_path, &final_path, cfg) {
+        info!("\tup to date: {}", final_path.display());
+        return Ok(meta);
+    }
+
+    if cfg.emit_sidecar {
+        write_sidecar(&meta, &out_path.with_extension("json"));
+    }
+
+    if cfg.output_format == OutputFormat::PlainText {
+        write_output(&final_path, &meta.text);
+        return Ok(meta);
+    }
+
+    let cached = if cfg.force { None } else { std::fs::read_to_string(&cache_path).ok() };
+    let post_html = if let Some(cached) = cached {
+        debug!("\trestored from cache: {}", cache_path.display());
+        cached
+    } else {
+        let issues_before = RENDER_ISSUES.get();
+        // render contents
+        let mut content = render_post_body(parsed, &front, "", &slug, cfg);
+        if cfg.embed_source {
+            content.push_str(&source_comment(&file));
+        }
+
+        // paste contents into template; front matter keys fill their own {{key}}
+        let nav = render_nav(&meta.url, cfg);
+        let edit_link = edit_url(in_path, cfg);
+        let extra = front.extra.iter()
+            .map(|(key, value)| (key.as_str(), value.as_str().map(str::to_string).unwrap_or_else(|| value.to_string())))
+            .collect::<Vec<_>>();
+        let escaped_title = escape_html(&title);
+        let mut vars = vec![
+            ("content", content.as_str()),
+            ("title", &escaped_title),
+            ("nav", &nav),
+            ("edit_url", &edit_link),
+            ("excerpt", &meta.excerpt),
+        ];
+        vars.extend(extra.iter().map(|(key, value)| (*key, value.as_str())));
+        let post_html = fill_template(&cfg.templates.post, &vars);
+
+        // a degraded render (missing image, failed math, ...) has to be redone, and warn again, next time
+        if RENDER_ISSUES.get() == issues_before {
+            write_cache(&cache_path, &post_html);
+        }
+        post_html
+    };
+
+    // write output to file
+    write_output(out_path, &post_html);
+    Ok(meta)
+}
+
+// a post that couldn't be compiled: with PostErrorPolicy::Skip it's reported and the rest of the
+// build goes on, with Abort it's handed back to stop the build
+fn post_failed(err: MinissgError, cfg: &CompilerConfig) -> Result<(), MinissgError> {
+    if cfg.on_post_error == PostErrorPolicy::Abort {
+        return Err(err);
+    }
+    error(&err.to_string());
+    Ok(())
+}
+
+// output file name: the file stem, or with slug_from_title the slugged front-matter title
+fn post_slug(in_path: &Path, cfg: &CompilerConfig) -> String {
+    let stem = in_path.file_stem().and_then(|s| s.to_str()).unwrap_or("untitled");
+    if cfg.slug_from_title
+        && let Ok(file) = std::fs::read_to_string(in_path)
+        && let Some(title) = split_front_matter(&file).0.title {
+        let slug = slugify(&title);
+        if !slug.is_empty() {
+            return slug;
+        }
+    }
+    stem.to_string()
+}
+
+// "My First Post!" => "my-first-post": lowercase, spaces to hyphens, other symbols dropped
+fn slugify(s: &str) -> String {
+    let mut slug = String::new();
+    for c in s.to_lowercase().chars() {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if (c.is_whitespace() || c == '-') && !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+// front matter, then (with title_from_h1) the first h1, then the file name
+fn post_title(front: &FrontMatter, blocks: &[Block], stem: &str, cfg: &CompilerConfig) -> String {
+    if let Some(title) = &front.title {
+        return title.clone();
+    }
+    if cfg.title_from_h1
+        && let Some(h1) = blocks.iter().find_map(|b| match b {
+            Block::Header(1, text) => Some(text),
+            _ => None,
+        }) {
+        return h1.clone();
+    }
+    stem.to_string()
+}
+
+// site-relative url a post is served from
+fn post_url(out_path: &Path, cfg: &CompilerConfig) -> String {
+    let file_name = out_path.file_name().and_then(|s| s.to_str()).unwrap_or("");
+    format!("{}/{}", cfg.posts_url.trim_end_matches('/'), file_name)
+}
+
+// e.g. https://github.com/user/repo/edit/main/posts/{{file}}
+fn edit_url(in_path: &Path, cfg: &CompilerConfig) -> String {
+    let Some(template) = &cfg.edit_url_template else { return String::new() };
+    let file = in_path.file_name().and_then(|s| s.to_str()).unwrap_or("");
+    template.replace("{{file}}", file)
+}
+
+fn render_nav(current_url: &str, cfg: &CompilerConfig) -> String {
+    if cfg.nav_links.is_empty() {
+        return String::new();
+    }
+    let mut s = String::from("<nav><ul>");
+    for (label, url) in &cfg.nav_links {
+        let current = if url == current_url { " aria-current=\"page\"" } else { "" };
+        s.push_str(&format!("<li><a href=\"{}\"{}>{}</a></li>", escape_url(url), current, escape_html(label)));
+    }
+    s.push_str("</ul></nav>");
+    s
+}
+
+// `-->` would end the comment early, so break it up
+fn source_comment(src: &str) -> String {
+    format!("\n<!-- source:\n{}\n-->\n", src.replace("-->", "--&gt;"))
+}
+
+fn warn(msg: &str) {
+    WARNINGS.fetch_add(1, Ordering::Relaxed);
+    RENDER_ISSUES.set(RENDER_ISSUES.get() + 1);
+    log::warn!("{}", msg);
+}
+
+fn error(msg: &str) {
+    ERRORS.fetch_add(1, Ordering::Relaxed);
+    RENDER_ISSUES.set(RENDER_ISSUES.get() + 1);
+    log::error!("{}", msg);
+}
+
+// creates missing parent directories, so a fresh checkout needs no www/posts/
+fn write_output(path: &Path, contents: &str) {
+    if let Some(dir) = path.parent()
+        && let Err(e) = std::fs::create_dir_all(dir) {
+        error(&format!("could not create {}: {}", dir.display(), e));
+        return;
+    }
+    if let Err(e) = std::fs::write(path, contents) {
+        error(&format!("could not write {}: {}", path.display(), e));
+    }
+}
+
+// h1 -> h3 etc. breaks the document outline
+fn lint_header_levels(blocks: &[Block]) {
+    let mut prev_level = 0;
+    for block in blocks {
+        if let Block::Header(level, src) = block {
+            if prev_level > 0 && *level > prev_level + 1 {
+                warn(&format!("header '{}' skips from h{} to h{}", src, prev_level, level));
+            }
+            prev_level = *level;
+        }
+    }
+}
+
+// a page should have one h1; later ones are optionally demoted to h2
+fn lint_multiple_h1s(blocks: &mut [Block], cfg: &CompilerConfig) {
+    let mut seen_h1 = false;
+    for block in blocks {
+        if let Block::Header(level, src) = block && *level == 1 {
+            if seen_h1 {
+                warn(&format!("more than one h1: '{}'", src));
+                if cfg.demote_extra_h1s {
+                    *level = 2;
+                }
+            }
+            seen_h1 = true;
+        }
+    }
+}
+
+// non-reserved keys only do anything if the template uses them, so flag typos
+fn lint_front_matter(front: &FrontMatter, cfg: &CompilerConfig) {
+    for key in front.extra.keys() {
+        if !cfg.templates.post.contains(&format!("{{{{{}}}}}", key)) {
+            warn(&format!("front matter key '{}' is not used by the template", key));
+        }
+    }
+}
+
+fn has_math(blocks: &[Block]) -> bool {
+    let texts_have_math = |texts: &[Text]| texts.iter().any(|t| t.fmt == TextFormat::InlineMath);
+    blocks.iter().any(|block| match block {
+        Block::Math(_) => true,
+        Block::Paragraph(texts) | Block::Footnote(_, texts) => texts_have_math(texts),
+        Block::Quote(lines) => lines.iter().any(|(_, texts)| texts_have_math(texts)),
+        Block::List(_, items) => items.iter()
+            .any(|item| texts_have_math(&item.content) || has_math(&item.blocks)),
+        Block::Table{headers, rows, ..} => std::iter::once(headers).chain(rows)
+            .any(|cells| cells.iter().any(|c| texts_have_math(c))),
+        Block::Container{content, ..} => has_math(content),
+        Block::DefinitionList(items) => items.iter()
+            .any(|item| item.terms.iter().chain(&item.definitions).any(|t| texts_have_math(t))),
+        _ => false,
+    })
+}
+
+// run once, and only for posts that actually contain math
+fn check_math_tooling(cfg: &CompilerConfig) {
+    static CHECKED: OnceLock<()> = OnceLock::new();
+    CHECKED.get_or_init(|| {
+        if cfg.math_template.is_empty() {
+            warn("math template is missing or empty");
+        }
+        let needs_latex = match cfg.math_backend {
+            MathBackend::Off => false,
+            MathBackend::Client => cfg.math_noscript_fallback,
+            _ => true,
+        };
+        if needs_latex && Command::new("latex").arg("--version").output().is_err() {
+            warn(&MinissgError::ToolMissing("latex".to_string()).to_string());
+        }
+    });
+}
+
+// 64-bit FNV-1a over length-prefixed fields, for cache keys that name files on disk: std's
+// hashers (and Hash impls) may change between toolchains, this can't
+struct StableHasher(u64);
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        StableHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl StableHasher {
+    fn field(&mut self, bytes: &[u8]) {
+        for b in (bytes.len() as u64).to_le_bytes().iter().chain(bytes) {
+            self.0 ^= u64::from(*b);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+// best effort; written aside and renamed so a concurrent reader never sees half a file
+fn write_cache(path: &Path, contents: &str) {
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    let tmp = path.with_extension(format!("{:?}.tmp", std::thread::current().id()));
+    if std::fs::write(&tmp, contents).is_ok() {
+        let _ = std::fs::rename(&tmp, path);
+    }
+}
+
+// output newer than its source and every (existing) template; config changes need --force
+fn is_up_to_date(in_path: &Path, out_path: &Path, cfg: &CompilerConfig) -> bool {
+    let mtime = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    let Some(built) = mtime(out_path) else {
+        return false;
+    };
+    std::iter::once(in_path)
+        .chain(cfg.template_paths.iter().map(PathBuf::as_path))
+        .all(|path| mtime(path).is_none_or(|t| t < built))
+}
+
+// hash of everything that affects a post's html: source, title, templates, settings, images
+fn render_cache_key(src: &str, title: &str, in_path: &Path, url: &str, blocks: &[Block],
+                    cfg: &CompilerConfig) -> String {
+    let mut hasher = StableHasher::default();
+    hasher.field(src.as_bytes());
+    hasher.field(title.as_bytes());
+    hasher.field(in_path.as_os_str().as_encoded_bytes());  // the edit link
+    hasher.field(url.as_bytes());                          // the current nav entry
+    // only what shows up in a post's html; paths, feeds and build policy don't
+    let settings: [&dyn std::fmt::Debug; 44] = [
+        &cfg.images_url, &cfg.images_dir, &cfg.templates.post, &cfg.math_template,
+        &cfg.math_font_package, &cfg.optimize_svg, &cfg.collapsible_sections, &cfg.max_list_depth,
+        &cfg.code_copy_button, &cfg.math_backend, &cfg.merge_subtitle, &cfg.embed_source,
+        &cfg.code_wrap, &cfg.alt_text_policy, &cfg.bibliography, &cfg.number_equations,
+        &cfg.number_figures, &cfg.posts_url, &cfg.nav_links, &cfg.code_lang_aliases,
+        &cfg.lead_paragraph, &cfg.show_code_lang, &cfg.math_noscript_fallback, &cfg.footnote_id_prefix,
+        &cfg.footnote_backref_symbol, &cfg.footnote_ref_separator, &cfg.image_thumbnails, &cfg.preserve_spaces,
+        &cfg.math_svg_class, &cfg.edit_url_template, &cfg.math_error_display, &cfg.excerpt_words,
+        &cfg.indent_width, &cfg.title_from_h1, &cfg.footnote_placement, &cfg.containers,
+        &cfg.code_tabs, &cfg.back_to_top, &cfg.syntax_highlighting, &cfg.autodetect_code_lang,
+        &cfg.demote_extra_h1s, &cfg.display_math_align, &cfg.empty_list_items, &cfg.slug_from_title,
+    ];
+    for setting in settings {
+        hasher.field(format!("{:?}", setting).as_bytes());
+    }
+    hasher.field(&[u8::from(cfg!(feature = "highlight"))]);
+    // dimensions and thumbnails come from the image files
+    for url in image_urls(blocks) {
+        let path = cfg.images_dir.join(url);
+        let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok()
+            .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok());
+        hasher.field(url.as_bytes());
+        hasher.field(&modified.map_or(0, |d| d.as_nanos()).to_le_bytes());
+    }
+    format!("{:016x}", hasher.finish())
+}
+
+fn image_urls(blocks: &[Block]) -> Vec<&str> {
+    blocks.iter().flat_map(|block| match block {
+        Block::Image(_, url, ..) => vec![url.as_str()],
+        Block::List(_, items) => items.iter().flat_map(|item| image_urls(&item.blocks)).collect(),
+        Block::Container{content, ..} => image_urls(content),
+        _ => Vec::new(),
+    }).collect()
+}
+
+
+/* ========================================
+                   parsing 
+   ======================================== */
+// front matter is fenced by `---` (yaml) or `+++` (toml); the fence picks the format
+fn split_front_matter(input: &str) -> (FrontMatter, &str) {
+    let mut lines = input.split_inclusive('\n');
+    let Some(first) = lines.next() else { return (FrontMatter::default(), input) };
+    let fence = first.trim_end();
+    if fence != "---" && fence != "+++" {
+        return (FrontMatter::default(), input);
+    }
+
+    // find the closing fence
+    let mut offset = first.len();
+    let mut front_src = None;
+    for line in lines {
+        if line.trim_end() == fence {
+            front_src = Some(&input[first.len()..offset]);
+            offset += line.len();
+            break;
+        }
+        offset += line.len();
+    }
+    let Some(front_src) = front_src else {
+        warn("unterminated front matter, treating as content");
+        return (FrontMatter::default(), input);
+    };
+    let body = &input[offset..];
+
+    let table = if fence == "+++" {
+        front_src.parse::<toml::Table>().map_err(|e| e.to_string())
+    } else {
+        parse_yaml_front_matter(front_src)
+    };
+    let front = table.and_then(|t| {
+        toml::Value::Table(t).try_into::<FrontMatter>().map_err(|e| e.to_string())
+    });
+    match front {
+        Ok(front) => (front, body),
+        Err(e) => {
+            warn(&format!("invalid front matter: {}", e.trim()));
+            (FrontMatter::default(), body)
+        }
+    }
+}
+
+// just enough yaml for front matter: `key: value`, inline `[a, b]` / `{k: v}`,
+// and `- item` lists under an empty key
+fn parse_yaml_front_matter(src: &str) -> Result<toml::Table, String> {
+    let mut table = toml::Table::new();
+    let mut list_key: Option<String> = None;
+
+    for line in src.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if let Some(item) = trimmed.strip_prefix("- ") && let Some(key) = &list_key {
+            if let Some(toml::Value::Array(items)) = table.get_mut(key) {
+                items.push(parse_yaml_scalar(item));
+            }
+            continue;
+        }
+        let Some((key, value)) = trimmed.split_once(':') else {
+            return Err(format!("expected `key: value`, got `{}`", trimmed));
+        };
+        let key = key.trim().to_string();
+        let value = value.trim();
+        if value.is_empty() {
+            table.insert(key.clone(), toml::Value::Array(Vec::new()));
+            list_key = Some(key);
+        } else {
+            table.insert(key, parse_yaml_value(value));
+            list_key = None;
+        }
+    }
+    Ok(table)
+}
+
+fn parse_yaml_value(value: &str) -> toml::Value {
+    if let Some(inner) = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+        let items = inner.split(',')
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(parse_yaml_scalar);
+        toml::Value::Array(items.collect())
+    } else if let Some(inner) = value.strip_prefix('{').and_then(|v| v.strip_suffix('}')) {
+        let mut table = toml::Table::new();
+        for pair in inner.split(',') {
+            if let Some((k, v)) = pair.split_once(':') {
+                table.insert(k.trim().to_string(), parse_yaml_scalar(v.trim()));
+            }
+        }
+        toml::Value::Table(table)
+    } else {
+        parse_yaml_scalar(value)
+    }
+}
+
+fn parse_yaml_scalar(value: &str) -> toml::Value {
+    let unquoted = value.strip_prefix('"').and_then(|v| v.strip_suffix('"'))
+        .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')));
+    if let Some(s) = unquoted {
+        return toml::Value::String(s.to_string());
+    }
+    match value {
+        "true" => toml::Value::Boolean(true),
+        "false" => toml::Value::Boolean(false),
+        _ => value.parse::<i64>()
+            .map(toml::Value::Integer)
+            .unwrap_or_else(|_| toml::Value::String(value.to_string())),
+    }
+}
+
+/// parses a post body (front matter already stripped) into blocks.
+///
+/// This takes a config (unlike a bare `parse(input)`) because the block structure depends on
+/// it: `indent_width` and `max_list_depth` decide list nesting, `empty_list_items` whether
+/// empty items are kept, and `alt_text_policy` how images without alt text are reported.
+/// [`CompilerConfig::default`] gives the stock behavior.
+pub fn parse(input: &str, cfg: &CompilerConfig) -> Vec<Block> {
+    // parse blocks
+    let blocks = parse_blocks(input.to_string(), cfg);
+
+    // postprocess text elements where needed
+    blocks.into_iter().map(parse_inner).collect()
+}
+
+fn parse_blocks(input: String, cfg: &CompilerConfig) -> Vec<Block> {
+    let mut blocks: Vec<Block> = Vec::new();
+    let mut lines = input.lines().peekable();
+    let mut text_buf = String::new();
+    let mut is_new_block = true;
+
+    while let Some(line) = lines.next() {
+        if line.trim().is_empty() {
+            flush_paragraph(&mut text_buf, &mut blocks);
+            is_new_block = true;
+            continue;
+        }
+
+        if !is_new_block && !interrupts_paragraph(line) {
+            if let Some(level) = setext_level(lines.peek()) {
+                text_buf.push_str(line);
+                blocks.push(Block::Header(level, text_buf.trim().to_string()));
+                text_buf = String::new();
+                lines.next();
+                continue;
+            }
+            text_buf.push_str(line);
+            text_buf.push(' ');     // spaces for newlines
+            continue;
+        }
+        // whatever was pending goes in before the block that cut it off
+        flush_paragraph(&mut text_buf, &mut blocks);
+
+        // headers
+        if line.starts_with("#") {  
+            let level = line.chars().take_while(|&c| c == '#').count();
+            let text = line[level..].trim();
+            // optional closing run, `## Title ##`; `C#` keeps its hash
+            let text = match text.trim_end_matches('#') {
+                t if t.is_empty() || t.ends_with(' ') => t.trim_end(),
+                _ => text,
+            }.to_string();
+            if text.is_empty() {
+                warn(&format!("skipping empty header: {}", line));
+            } else {
+                blocks.push(Block::Header(level, text));
+            }
+        } 
+
+        // code block
+        else if let Some(info) = line.strip_prefix("```") {
+            let (language, caption) = parse_code_info(info);
+            for line in lines.by_ref() {
+                if line.starts_with("```") { break; }
+                text_buf.push_str(line);
+                text_buf.push('\n');
+            }
+            blocks.push(Block::Code(language, trim_blank_lines(&text_buf), caption));
+            text_buf = String::new();
+        } 
+
+        // math block
+        else if line.starts_with("\\[") {
+            for line in lines.by_ref() {
+                if line.starts_with("\\]") { break; }
+                text_buf.push_str(line);
+                text_buf.push('\n');
+            }
+            blocks.push(Block::Math(text_buf.clone()));
+            text_buf = String::new();
+        } 
+
+        // fenced containers, which may nest
+        else if let Some(name) = line.strip_prefix(":::").map(str::trim).filter(|n| !n.is_empty()) {
+            let mut depth = 1;
+            let mut inner = String::new();
+            for line in lines.by_ref() {
+                if line.trim() == ":::" {
+                    depth -= 1;
+                    if depth == 0 { break; }
+                } else if line.starts_with(":::") {
+                    depth += 1;
+                }
+                inner.push_str(line);
+                inner.push('\n');
+            }
+            blocks.push(Block::Container{name: name.to_string(), content: parse(&inner, cfg)});
+        }
+
+        // standalone images
+        else if line.starts_with("![") {
+            if let Some(caps) = IMAGE_REGEX.captures(line) {
+                let url = caps[2].trim().to_string();
+                let width = caps.get(3)
+                    .and_then(|m| m.as_str().parse::<u32>().ok())
+                    .unwrap_or(100);
+                let unit = caps.get(4).map_or("%", |m| m.as_str()).to_string();
+                if url.is_empty() {
+                    warn(&format!("skipping image with empty source: {}", line));
+                } else {
+                    let alt = image_alt(caps[1].trim(), &url, cfg);
+                    blocks.push(Block::Image(alt, url, width, unit));
+                }
+            }
+        }
+
+        // shortcodes; unknown ones fall through and stay literal
+        else if let Some(caps) = SHORTCODE_REGEX.captures(line.trim())
+            && let Some(html) = render_shortcode(&caps[1], &caps[2]) {
+            blocks.push(Block::Html(html));
+        }
+
+        // comments
+        else if line.starts_with("<!--") {
+            for line in lines.by_ref() {
+                if line.starts_with("-->") {
+                    break;
+                }
+            }
+        }
+
+        // raw html
+        else if line.starts_with("<html>") {
+            let mut buf = String::new();
+            for line in lines.by_ref() {
+                if line.starts_with("</html>") {
+                    break;
+                }
+                buf.push_str(line);
+            }
+            blocks.push(Block::Html(buf));
+        }
+
+        // block quotes, one level per '>' (see captures_quote); runs until a non-quote line
+        else if let Some(first) = captures_quote(line) {
+            let mut quote = vec![first];
+            while let Some(next) = lines.peek().and_then(|l| captures_quote(l)) {
+                quote.push(next);
+                lines.next();
+            }
+            blocks.push(Block::Quote(quote.into_iter().map(|(depth, src)| (depth, parse_text(src))).collect()));
+        }
+
+        // table of contents, filled in once every header has its id
+        else if line.trim() == "[[TOC]]" {
+            blocks.push(Block::Toc);
+        }
+
+        // list of figures, filled in like the toc
+        else if line.trim() == "{{lof}}" {
+            blocks.push(Block::Lof);
+        }
+
+        // footnote defns (single line for now)
+        else if line.starts_with("[^") {
+            if line.trim() == "[^]" {
+                blocks.push(Block::FootnoteFlush);
+            } else if let Some(caps) = FOOTNOTE_DEFN_REGEX.captures(line) {
+                let footnote_id = caps[1].to_string();
+                let contents = caps[2].to_string();
+                blocks.push(
+                    Block::Footnote(
+                        footnote_id,
+                        vec![Text {src: contents, fmt: TextFormat::Raw, marks: Vec::new()}])
+                );
+            }
+        }
+
+        // pipe tables: a header row followed by a |---|:--:| delimiter row
+        else if line.starts_with('|')
+            && let Some(align) = lines.peek().and_then(|l| parse_table_delim(l)) {
+            lines.next();
+            let headers = split_table_row(line).into_iter().map(parse_text).collect::<Vec<_>>();
+            let mut rows = Vec::new();
+            while let Some(&row) = lines.peek() && row.starts_with('|') {
+                let mut cells = split_table_row(row).into_iter().map(parse_text).collect::<Vec<_>>();
+                cells.resize_with(headers.len(), Vec::new);
+                rows.push(cells);
+                lines.next();
+            }
+            blocks.push(Block::Table{headers, rows, align});
+        }
+
+        // ordered lists 
+        else if let Some(li0) = captures_ol_li(line, cfg) {
+            let items = parse_list_items(li0, &mut lines, cfg);
+            if !items.is_empty() {
+                blocks.push(Block::List(true, items));
+            }
+        }
+
+        // unordered lists
+        else if let Some(li0) = captures_ul_li(line, cfg) {
+            let items = parse_list_items(li0, &mut lines, cfg);
+            if !items.is_empty() {
+                blocks.push(Block::List(false, items));
+            }
+        }
+
+        // definition lists: term lines, then `: definition` lines
+        else if starts_definition_list(line, &lines) {
+            blocks.push(Block::DefinitionList(parse_definition_items(line, &mut lines)));
+        }
+
+        // setext header: a text line underlined by `===` or `---`
+        else if let Some(level) = setext_level(lines.peek()) {
+            blocks.push(Block::Header(level, line.trim().to_string()));
+            lines.next();
+        }
+
+        // paragraph
+        else {
+            text_buf.push_str(line);
+            text_buf.push(' ');
+        }
+        is_new_block = false;
+    }
+
+    flush_paragraph(&mut text_buf, &mut blocks);
+    blocks
+}
+
+fn flush_paragraph(text_buf: &mut String, blocks: &mut Vec<Block>) {
+    if !text_buf.is_empty() {
+        blocks.push(
+            Block::Paragraph(vec![Text {src: std::mem::take(text_buf), fmt: TextFormat::Raw, marks: Vec::new()}])
+        );
+    }
+}
+
+// blocks that may start right after a paragraph line, without a blank in between
+fn interrupts_paragraph(line: &str) -> bool {
+    let hashes = line.chars().take_while(|&c| c == '#').count();
+    (hashes > 0 && line[hashes..].starts_with(' '))
+        || line.starts_with("```")
+        || line.starts_with("\\[")
+        || line.starts_with("<html>")
+        || captures_quote(line).is_some()
+}
+
+fn render_shortcode(name: &str, id: &str) -> Option<String> {
+    let src = match name {
+        "youtube" => format!("https://www.youtube.com/embed/{}", id),
+        "vimeo" => format!("https://player.vimeo.com/video/{}", id),
+        _ => {
+            warn(&format!("unknown shortcode '{}'", name));
+            return None;
+        }
+    };
+    Some(format!(
+        "<div class=\"video\"><iframe src=\"{}\" title=\"{} video\" frameborder=\"0\" allowfullscreen></iframe></div>\n",
+        src, name
+    ))
+}
+
+// the line and any that follow it up to a `: ` line (with no blank in between) are terms
+fn starts_definition_list(line: &str, lines: &Peekable<Lines>) -> bool {
+    if line.starts_with(':') {
+        return false;
+    }
+    for next in lines.clone() {
+        if next.trim().is_empty() {
+            return false;
+        }
+        if next.starts_with(": ") {
+            return true;
+        }
+    }
+    false
+}
+
+// consumes through the end of the list; stops (without consuming) at a blank line
+fn parse_definition_items(first_term: &str, lines: &mut Peekable<Lines>) -> Vec<DefinitionItem> {
+    let mut items = vec![DefinitionItem{terms: vec![parse_text(first_term.trim().to_string())], definitions: Vec::new()}];
+    while let Some(&line) = lines.peek() && !line.trim().is_empty() {
+        let item = items.last_mut().unwrap();
+        if let Some(definition) = line.strip_prefix(": ") {
+            item.definitions.push(parse_text(definition.trim().to_string()));
+        } else if item.definitions.is_empty() {
+            item.terms.push(parse_text(line.trim().to_string()));
+        } else {
+            items.push(DefinitionItem{terms: vec![parse_text(line.trim().to_string())], definitions: Vec::new()});
+        }
+        lines.next();
+    }
+    items
+}
+
+fn split_table_row(line: &str) -> Vec<String> {
+    let line = line.trim();
+    let line = line.strip_prefix('|').unwrap_or(line);
+    let line = line.strip_suffix('|').unwrap_or(line);
+    line.split('|').map(|cell| cell.trim().to_string()).collect()
+}
+
+// `|---|:--:|--:|`, or None if the line isn't a delimiter row
+fn parse_table_delim(line: &str) -> Option<Vec<Alignment>> {
+    if !line.starts_with('|') {
+        return None;
+    }
+    split_table_row(line).iter().map(|cell| {
+        let dashes = cell.trim_start_matches(':').trim_end_matches(':');
+        if dashes.is_empty() || !dashes.chars().all(|c| c == '-') {
+            return None;
+        }
+        Some(match (cell.starts_with(':'), cell.ends_with(':')) {
+            (true, true) => Alignment::Center,
+            (true, false) => Alignment::Left,
+            (false, true) => Alignment::Right,
+            (false, false) => Alignment::None,
+        })
+    }).collect()
+}
+
+// "rust", "rust:src/main.rs", ":Dockerfile" or a bare "main.py" => (language, caption);
+// with no language given, one is inferred from the caption's file name
+fn parse_code_info(info: &str) -> (String, Option<String>) {
+    let info = info.trim();
+    let (lang, caption) = match info.split_once(':') {
+        Some((lang, caption)) => (lang.trim(), caption.trim()),
+        None if infer_code_lang(info).is_some() => ("", info),
+        None => (info, ""),
+    };
+    let caption = (!caption.is_empty()).then(|| caption.to_string());
+    let lang = match (lang, &caption) {
+        ("", Some(caption)) => infer_code_lang(caption).unwrap_or_default(),
+        _ => lang,
+    };
+    (lang.to_string(), caption)
+}
+
+// only for things that look like file names, so "python" stays a language
+fn infer_code_lang(name: &str) -> Option<&'static str> {
+    let file_name = name.rsplit('/').next().unwrap_or(name);
+    let by_name = match file_name {
+        "Makefile" | "GNUmakefile" => Some("make"),
+        "Dockerfile" | "Containerfile" => Some("dockerfile"),
+        "CMakeLists.txt" => Some("cmake"),
+        "Gemfile" | "Rakefile" => Some("ruby"),
+        _ => None,
+    };
+    by_name.or_else(|| match file_name.rsplit_once('.')?.1 {
+        "py" => Some("python"),
+        "rs" => Some("rust"),
+        "js" | "mjs" => Some("javascript"),
+        "ts" => Some("typescript"),
+        "sh" | "bash" => Some("bash"),
+        "c" | "h" => Some("c"),
+        "cpp" | "cc" | "hpp" => Some("cpp"),
+        "go" => Some("go"),
+        "rb" => Some("ruby"),
+        "java" => Some("java"),
+        "hs" => Some("haskell"),
+        "tex" => Some("latex"),
+        "html" => Some("html"),
+        "css" => Some("css"),
+        "json" => Some("json"),
+        "toml" => Some("toml"),
+        "yaml" | "yml" => Some("yaml"),
+        "md" => Some("markdown"),
+        "mk" => Some("make"),
+        "dockerfile" => Some("dockerfile"),
+        _ => None,
+    })
+}
+
+// drops blank lines before the first and after the last real line of a code block;
+// interior blanks stay
+fn trim_blank_lines(src: &str) -> String {
+    let lines = src.lines().collect::<Vec<_>>();
+    let Some(first) = lines.iter().position(|l| !l.trim().is_empty()) else {
+        return String::new();
+    };
+    let last = lines.iter().rposition(|l| !l.trim().is_empty()).unwrap();
+    let mut out = lines[first..=last].join("\n");
+    out.push('\n');
+    out
+}
+
+// `===` underlines an h1, `---` an h2; it takes at least two, a lone `-` is an empty list item
+fn setext_level(next: Option<&&str>) -> Option<usize> {
+    let next = next?.trim();
+    if next.len() < 2 {
+        None
+    } else if next.chars().all(|c| c == '=') {
+        Some(1)
+    } else if next.chars().all(|c| c == '-') {
+        Some(2)
+    } else {
+        None
+    }
+}
+
+// every `>` is a level: `> a` is depth 1, `> > a` depth 2. the older doubled marker still
+// counts once, so `>> a` is depth 1 and `>>>> a` or `>> >> a` depth 2
+fn captures_quote(line: &str) -> Option<(usize, String)> {
+    if !line.starts_with('>') {
+        return None;
+    }
+    let mut depth = 0;
+    let mut rest = line;
+    while rest.trim_start_matches(' ').starts_with('>') {
+        let run = rest.trim_start_matches(' ');
+        let n = run.len() - run.trim_start_matches('>').len();
+        depth += n.div_ceil(2);
+        rest = &run[n..];
+    }
+    if !rest.is_empty() && !rest.starts_with(' ') {
+        return None;
+    }
+    Some((depth, rest.trim().to_string()))
+}
+
+// consumes list lines following the first item; stops (without consuming) at the first non-list line
+fn parse_list_items(li0: ListItem, lines: &mut Peekable<Lines>, cfg: &CompilerConfig) -> Vec<ListItem> {
+    let mut items = vec![li0];
+    while let Some(&line) = lines.peek() {
+        if let Some(item) = captures_ol_li(line, cfg).or_else(|| captures_ul_li(line, cfg)) {
+            items.push(item);
+            lines.next();
+        }
+        // fenced code indented under an item belongs to that item
+        else if line.starts_with(' ') && line.trim_start().starts_with("```") {
+            let indent = line.len() - line.trim_start_matches([' ', '\t']).len();
+            let (language, caption) = parse_code_info(&line.trim_start()[3..]);
+            lines.next();
+            let mut src = String::new();
+            for line in lines.by_ref() {
+                if line.trim_start().starts_with("```") { break; }
+                // strip up to the fence's indent, never into the code itself
+                let leading = line.len() - line.trim_start_matches([' ', '\t']).len();
+                src.push_str(&line[leading.min(indent)..]);
+                src.push('\n');
+            }
+            items.last_mut().unwrap().blocks.push(Block::Code(language, trim_blank_lines(&src), caption));
+        }
+        // indented past the item's marker => continuation, whatever it looks like
+        else if let Some(item) = items.last_mut()
+            && !line.trim().is_empty()
+            && line.len() - line.trim_start().len() > item.level * cfg.indent_width {
+            item.content.push(Text{src: " ".to_string(), fmt: TextFormat::Plain, marks: Vec::new()});
+            item.content.extend(parse_text(line.trim().to_string()));
+            lines.next();
+        } else {
+            break;
+        }
+    }
+    drop_empty_list_items(&mut items, cfg);
+    clamp_list_depth(&mut items, cfg.max_list_depth);
+    items
+}
+
+fn drop_empty_list_items(items: &mut Vec<ListItem>, cfg: &CompilerConfig) {
+    items.retain(|item| {
+        if item.blocks.is_empty() && item.content.iter().all(|t| t.src.trim().is_empty()) {
+            warn("empty list item");
+            return cfg.empty_list_items == EmptyListItemPolicy::Keep;
+        }
+        true
+    });
+}
+
+// nesting level for a marker indented by `indent` spaces; shared by both list kinds so they agree
+fn list_level(indent: usize, cfg: &CompilerConfig) -> usize {
+    indent / cfg.indent_width.max(1)
+}
+
+fn captures_ol_li(line: &str, cfg: &CompilerConfig) -> Option<ListItem> {
+    if let Some(caps) = OL_LI_REGEX.captures(line) {
+        let level = list_level(caps[1].len(), cfg);
+        let delimiter = caps[3].chars().next().unwrap();
+        let content = parse_text(caps[4].to_string());
+        Some(ListItem{level, delimiter, content, blocks: Vec::new()})
+    } else {
+        None
+    }
+}
+
+fn captures_ul_li(line: &str, cfg: &CompilerConfig) -> Option<ListItem> {
+    if let Some(caps) = UL_LI_REGEX.captures(line) {
+        let level = list_level(caps[1].len(), cfg);
+        let delimiter = caps[2].chars().next().unwrap();
+        let content = parse_text(caps[3].to_string());
+        Some(ListItem{level, delimiter, content, blocks: Vec::new()})
+    } else {
+        None
+    }
+}
+
+fn image_alt(alt: &str, url: &str, cfg: &CompilerConfig) -> String {
+    if !alt.is_empty() {
+        return alt.to_string();
+    }
+    let msg = format!("image '{}' has no alt text", url);
+    if cfg.alt_text_policy == AltTextPolicy::Derive {
+        // sunset_over-lake.png => "sunset over lake"
+        Path::new(url).file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("")
+            .replace(['-', '_'], " ")
+    } else if cfg.alt_text_policy == AltTextPolicy::Error {
+        error(&msg);
+        String::new()
+    } else {
+        warn(&msg);
+        String::new()
+    }
+}
+
+// guards the renderer against pathologically deep (usually malformed) nesting
+fn clamp_list_depth(items: &mut [ListItem], max_depth: usize) {
+    for item in items.iter_mut() {
+        if item.level > max_depth {
+            warn(&format!("list nesting level {} exceeds max of {}; flattening",
+                item.level, max_depth));
+            item.level = max_depth;
+        }
+    }
+}
+
+// some blocks need postprocessing
+fn parse_inner(block: Block) -> Block {
+    match block {
+        Block::Paragraph(ts) => {
+            // assume its raw in this pass
+            if let Some(raw_text) = ts.first() {
+                // text is escaped, so html outside an <html> block shows up as text; it used to pass through
+                let mut start = raw_text.src.chars();
+                if start.next() == Some('<') && start.next().is_some_and(|c| c.is_ascii_alphabetic()) {
+                    warn(&format!("paragraph starting {} is shown as text; wrap raw html in <html> ... </html>",
+                        raw_text.src.split_whitespace().next().unwrap_or("")));
+                }
+                // drop the space that stood in for the last newline
+                Block::Paragraph(parse_text(raw_text.src.trim_end().to_string()))
+            } else {
+                Block::Paragraph(ts)
+            }
+        },
+        Block::Footnote(id, ts) => {
+            if let Some(raw_text) = ts.first() {
+                Block::Footnote(id, parse_text(raw_text.src.clone()))
+            } else {
+                Block::Footnote(id, ts)
+            }
+        }
+        _ => block 
+    }
+}
+
+fn parse_text(src: String) -> Vec<Text> {
+    let mut chars = src.chars();
+    let mut s_buf = String::new();
+    let mut texts = Vec::new();
+    let mut escaped = false;
+    let mut fmt = TextFormat::Plain;
+    let mut marks = Vec::new();
+    let mut in_literal_mode = false;
+
+    loop {
+        let rest = chars.as_str();
+        let Some(c) = chars.next() else { break };
+        if escaped {
+            s_buf.push(c);
+            escaped = false;
+            continue;
+        } else if in_literal_mode { // todo deconflate
+            if (c == '$' && fmt == TextFormat::InlineMath)
+                || (c == '`' && fmt == TextFormat::InlineCode) {
+                push_fmted_text(&mut s_buf, &mut texts, &marks, &mut fmt, TextFormat::Plain);
+                in_literal_mode = false;
+            } else {
+                s_buf.push(c);
+            }
+            continue;
+        }
+        // links and bare urls are taken whole, so a `_` or `*` in a url can't start emphasis;
+        // push_plain_text picks them out again
+        if c == '[' || (c == 'h' && !s_buf.ends_with(|p: char| p.is_alphanumeric())) {
+            let link = LINK_START_REGEX.find(rest).map(|m| m.as_str())
+                .or_else(|| BARE_URL_START_REGEX.find(rest).map(|m| trim_bare_url(m.as_str())));
+            if let Some(link) = link {
+                s_buf.push_str(link);
+                chars = rest[link.len()..].chars();
+                continue;
+            }
+        }
+        match c { 
+            '\\' => { escaped = true; }
+            '%' if chars.as_str().starts_with('%') => {
+                // %% inline comment %%, dropped from output
+                chars.next();
+                let comment = take_inline_comment(&mut chars);
+                if let Some(unterminated) = comment {
+                    s_buf.push_str("%%");
+                    s_buf.push_str(&unterminated);
+                }
+            }
+            '*' => {
+                let delim = if chars.as_str().starts_with('*') { chars.next(); "**" } else { "*" };
+                toggle_mark(delim, TextFormat::Bold, &chars, &mut s_buf, &mut texts, &mut marks);
+            }
+            '_' => { toggle_mark("_", TextFormat::Italic, &chars, &mut s_buf, &mut texts, &mut marks); }
+            '~' if chars.as_str().starts_with('~') => {
+                chars.next();
+                toggle_mark("~~", TextFormat::Strikethrough, &chars, &mut s_buf, &mut texts, &mut marks);
+            }
+            '$' => {
+                push_fmted_text(&mut s_buf, &mut texts, &marks, &mut fmt, TextFormat::InlineMath);
+                in_literal_mode = !in_literal_mode;
+            }
+            '`' => {
+                push_fmted_text(&mut s_buf, &mut texts, &marks, &mut fmt, TextFormat::InlineCode);
+                in_literal_mode = !in_literal_mode;
+            }
+            _ => { s_buf.push(c); }
+        }
+    }
+    let f2 = fmt.clone();
+    push_fmted_text(&mut s_buf, &mut texts, &marks, &mut fmt, f2);
+    // texts.push(Text{src: s_buf, fmt: fmt.clone()});
+    texts
+}
+
+// consumes through the closing `%%`; hands back the text if there isn't one
+fn take_inline_comment(chars: &mut Chars) -> Option<String> {
+    let rest = chars.as_str();
+    match rest.find("%%") {
+        Some(end) => {
+            *chars = rest[end + 2..].chars();
+            None
+        }
+        None => {
+            *chars = "".chars();
+            Some(rest.to_string())
+        }
+    }
+}
+
+// also responsible for postprocessing links/footnotes/citations
+fn push_fmted_text( s_buf: &mut String, texts: &mut Vec<Text>, marks: &[TextFormat],
+                    fmt_c: &mut TextFormat, fmt_new: TextFormat){
+    // even an empty run must toggle
+    if *fmt_c == TextFormat::Plain {
+        push_marked_text(s_buf, texts, marks);
+    } else if !s_buf.is_empty() {
+        texts.push(Text{src: s_buf.clone(), fmt: fmt_c.clone(), marks: marks.to_vec()});
+    }
+    *fmt_c = if *fmt_c == fmt_new { TextFormat::Plain } else {fmt_new};
+    *s_buf = String::new();
+}
+
+// a run outside code/math; push_plain_text picks out its references
+fn push_marked_text(s_buf: &mut String, texts: &mut Vec<Text>, marks: &[TextFormat]) {
+    if !s_buf.is_empty() {
+        push_plain_text(s_buf, texts, marks);
+    }
+    *s_buf = String::new();
+}
+
+// inside marks the innermost one is the format of plain text
+fn marked_text(src: &str, marks: &[TextFormat]) -> Text {
+    match marks.split_last() {
+        None => Text{src: src.to_string(), fmt: TextFormat::Plain, marks: Vec::new()},
+        Some((fmt, outer)) => Text{src: src.to_string(), fmt: fmt.clone(), marks: outer.to_vec()},
+    }
+}
+
+// a delimiter closes its mark (and anything opened inside it) if open, else opens it.
+// an opener with no closer later on stays literal instead of eating the line
+fn toggle_mark(delim: &str, mark: TextFormat, rest: &Chars,
+               s_buf: &mut String, texts: &mut Vec<Text>, marks: &mut Vec<TextFormat>) {
+    if let Some(i) = marks.iter().rposition(|m| *m == mark) {
+        push_marked_text(s_buf, texts, marks);
+        marks.truncate(i);
+    } else if rest.as_str().contains(delim) {
+        push_marked_text(s_buf, texts, marks);
+        marks.push(mark);
+    } else {
+        s_buf.push_str(delim);
+    }
+}
+
+// trailing punctuation belongs to the sentence, and so does a `)` that doesn't close a `(`
+// in the url
+fn trim_bare_url(url: &str) -> &str {
+    let mut url = url;
+    loop {
+        let trimmed = url.trim_end_matches(['_', '.', ',', ';', ':', '!', '?', ']', '\'', '"']);
+        let trimmed = match trimmed.strip_suffix(')') {
+            Some(inner) if trimmed.matches('(').count() < trimmed.matches(')').count() => inner,
+            _ => trimmed,
+        };
+        if trimmed.len() == url.len() {
+            return url;
+        }
+        url = trimmed;
+    }
+}
+
+// splits a run around inline references, earliest match first; everything keeps the
+// marks (bold, italic, ...) the run is in
+fn push_plain_text(src: &str, texts: &mut Vec<Text>, marks: &[TextFormat]) {
+    let ref_regexes = [&*LINK_REGEX, &*FOOTNOTE_REF_REGEX, &*CITATION_REGEX, &*BARE_URL_REGEX];
+    let mut rest = src;
+    while !rest.is_empty() {
+        let next = ref_regexes.iter().enumerate()
+            .filter_map(|(i, r)| r.captures(rest).map(|caps| (i, caps)))
+            .min_by_key(|(_, caps)| caps.get(0).unwrap().start());
+        let Some((kind, caps)) = next else {
+            texts.push(marked_text(rest, marks));
+            break;
+        };
+
+        // push stuff before the reference if it exists
+        let mat = caps.get(0).unwrap();
+        let mut end = mat.end();
+        if mat.start() > 0 {
+            texts.push(marked_text(&rest[..mat.start()], marks));
+        }
+
+        match kind {
+            // link
+            0 => {
+                let link_text = caps[1].trim().to_string();
+                let url = caps[2].trim().to_string();
+                if link_text.is_empty() || url.is_empty() {
+                    // degrade to plain text rather than emitting a broken anchor
+                    warn(&format!("empty link text or url in '{}'", &caps[0]));
+                    let plain = if link_text.is_empty() { url } else { link_text };
+                    if !plain.is_empty() {
+                        texts.push(marked_text(&plain, marks));
+                    }
+                } else {
+                    let title = caps.get(3).map(|m| m.as_str().to_string());
+                    texts.push(Text{src: link_text, fmt: TextFormat::Link(url, title), marks: marks.to_vec()});
+                }
+            }
+            // footnote ref
+            1 => {
+                if mat.start() == 0 && texts.last().is_some_and(|t| t.fmt == TextFormat::FootnoteRef) {
+                    texts.push(Text{src: String::new(), fmt: TextFormat::FootnoteSeparator, marks: marks.to_vec()});
+                }
+                texts.push(Text{src: caps[1].to_string(), fmt: TextFormat::FootnoteRef, marks: marks.to_vec()});
+            }
+            // citation
+            2 => texts.push(Text{src: caps[1].to_string(), fmt: TextFormat::Citation, marks: marks.to_vec()}),
+            // bare url
+            _ => {
+                let url = trim_bare_url(mat.as_str());
+                end = mat.start() + url.len();
+                texts.push(Text{src: url.to_string(), fmt: TextFormat::Link(url.to_string(), None), marks: marks.to_vec()});
+            }
+        }
+        rest = &rest[end..];
+    }
+}
+
+
+/* ========================================
+                    rendering
+   ======================================== */
+/// html for parsed blocks, without front matter or the page template
+pub fn render_document(blocks: Vec<Block>, cfg: &CompilerConfig) -> String {
+    render_post_body(blocks, &FrontMatter::default(), "", "", cfg)
+}
+
+// the front matter dir, if it's one html knows
+fn text_direction(front: &FrontMatter) -> Option<String> {
+    let dir = front.dir.as_deref()?;
+    if !matches!(dir, "ltr" | "rtl" | "auto") {
+        warn(&format!("unknown text direction '{}', expected ltr, rtl or auto", dir));
+        return None;
+    }
+    Some(dir.to_string())
+}
+
+// id_namespace prefixes generated ids, for pages holding more than one document
+fn render_post_body(blocks: Vec<Block>, front: &FrontMatter,
+                   id_namespace: &str, slug: &str, cfg: &CompilerConfig) -> String {
+    let state = &mut RenderState {
+        id_namespace: id_namespace.to_string(),
+        slug: slug.to_string(),
+        code_lang_map: front.code_lang_map.clone(),
+        dir: text_direction(front),
+        ..Default::default()
+    };
+    let mut s = String::new();
+    if cfg.back_to_top {
+        s.push_str(&format!("<a id=\"{}top\"></a>\n", id_namespace));
+    }
+    let mut in_section = false;
+    let mut footnotes = String::new();  // held back unless placed in place
+    let mut i = 0;
+    while i < blocks.len() {
+        let block = &blocks[i];
+
+        // h1 immediately followed by h2 => title/subtitle pair
+        if cfg.merge_subtitle
+            && let Block::Header(1, title) = block
+            && let Some(Block::Header(2, subtitle)) = blocks.get(i + 1) {
+            // anchored and listed in the toc under the title
+            let id = header_id(title, state);
+            state.headers.push((1, title.clone(), id.clone()));
+            s.push_str(&format!(
+                "<header class=\"title-block\"><h1 id=\"{}\" class=\"title\">{} <a href=\"#{}\" class=\"header-anchor\">#</a></h1>\
+                <h2 class=\"subtitle\">{}</h2></header>\n<hr><br>",
+                id, render_header_text(title), id, render_header_text(subtitle)
+            ));
+            i += 2;
+            continue;
+        }
+
+        // runs of adjacent labeled code blocks => one tab group
+        if cfg.code_tabs {
+            let run = blocks[i..].iter()
+                .take_while(|b| matches!(b, Block::Code(lang, ..) if !lang.is_empty()))
+                .count();
+            if run >= 2 {
+                s.push_str(&render_code_tabs(&blocks[i..i + run], cfg, state));
+                i += run;
+                continue;
+            }
+        }
+
+        match block {
+            Block::Footnote(..) if cfg.footnote_placement == FootnotePlacement::End
+                || cfg.footnote_placement == FootnotePlacement::Marker => {
+                footnotes.push_str(&block.render(cfg, state));
+                footnotes.push('\n');
+            }
+            Block::FootnoteFlush if cfg.footnote_placement == FootnotePlacement::Marker => {
+                s.push_str(&render_footnote_section(std::mem::take(&mut footnotes)));
+            }
+            // an h2 opens a section that runs until the next h1/h2
+            Block::Header(level, _) if cfg.collapsible_sections && *level <= 2 => {
+                if in_section {
+                    s.push_str("</details>\n");
+                    in_section = false;
+                }
+                if *level == 2 {
+                    s.push_str(&format!("<details open><summary>{}</summary>\n", block.render(cfg, state).trim_end()));
+                    in_section = true;
+                } else {
+                    s.push_str(&block.render(cfg, state));
+                }
+            }
+            _ => s.push_str(&block.render(cfg, state)),
+        }
+        i += 1;
+    }
+    if in_section {
+        s.push_str("</details>\n");
+    }
+    s.push_str(&render_footnote_section(footnotes));
+    s.push_str(&render_bibliography(cfg, state));
+    if cfg.back_to_top {
+        s.push_str(&format!("<a href=\"#{}top\" class=\"back-to-top\">back to top ↑</a>\n", id_namespace));
+    }
+    // second pass: headers after the marker count too
+    if s.contains(TOC_PLACEHOLDER) {
+        s = s.replace(TOC_PLACEHOLDER, &render_toc(&state.headers));
+    }
+    if s.contains(LOF_PLACEHOLDER) {
+        s = s.replace(LOF_PLACEHOLDER, &render_lof(&state.figures));
+    }
+    s
+}
+
+const TOC_PLACEHOLDER: &str = "<!-- minissg:toc -->";
+const LOF_PLACEHOLDER: &str = "<!-- minissg:lof -->";
+
+fn render_lof(figures: &[(usize, String, String)]) -> String {
+    if figures.is_empty() {
+        return String::new();
+    }
+    let mut s = String::from("<nav class=\"lof\"><ul>");
+    for (n, caption, id) in figures {
+        s.push_str(&format!("<li><a href=\"#{}\">Figure {}</a>: {}</li>", id, n, caption));
+    }
+    s.push_str("</ul></nav>\n");
+    s
+}
+
+// nested by level, relative to the shallowest header
+fn render_toc(headers: &[(usize, String, String)]) -> String {
+    let Some(base) = headers.iter().map(|(level, ..)| *level).min() else {
+        return String::new();
+    };
+    let mut s = String::from("<nav class=\"toc\"><ul>");
+    let mut current = 0;
+    for (i, (level, src, id)) in headers.iter().enumerate() {
+        let level = level - base;
+        if level > current {
+            // a skipped level still gets an (empty) item, a <ul> can't sit directly in a <ul>
+            if i == 0 {
+                s.push_str("<li>");
+            }
+            s.push_str(&"<ul><li>".repeat(level - current - 1));
+            s.push_str("<ul>");
+        } else if level < current {
+            s.push_str("</li>");
+            s.push_str(&"</ul></li>".repeat(current - level));
+        } else if i > 0 {
+            s.push_str("</li>");
+        }
+        s.push_str(&format!("<li><a href=\"#{}\">{}</a>", id, render_header_text(src)));
+        current = level;
+    }
+    s.push_str("</li>");
+    s.push_str(&"</ul></li>".repeat(current));
+    s.push_str("</ul></nav>\n");
+    s
+}
+
+// buttons carry data-tab, panels the matching data-tab; all but the first start hidden
+fn render_code_tabs(blocks: &[Block], cfg: &CompilerConfig, state: &mut RenderState) -> String {
+    let mut labels = String::new();
+    let mut panels = String::new();
+    for (n, block) in blocks.iter().enumerate() {
+        let label = match block {
+            Block::Code(lang, ..) if !lang.is_empty() => lang.clone(),
+            _ => (n + 1).to_string(),
+        };
+        let selected = n == 0;
+        labels.push_str(&format!(
+            "<button role=\"tab\" aria-selected=\"{}\" data-tab=\"{}\">{}</button>",
+            selected, n, escape_html(&label)
+        ));
+        panels.push_str(&format!(
+            "<div class=\"tab-panel\" role=\"tabpanel\" data-tab=\"{}\"{}>{}</div>\n",
+            n, if selected { "" } else { " hidden" }, block.render(cfg, state)
+        ));
+    }
+    format!(
+        "<div class=\"code-tabs\">\n<div class=\"tab-labels\" role=\"tablist\">{}</div>\n{}</div>\n",
+        labels, panels
+    )
+}
+
+fn render_footnote_section(footnotes: String) -> String {
+    if footnotes.is_empty() {
+        return String::new();
+    }
+    format!("<section class=\"footnotes\">\n{}</section>\n", footnotes)
+}
+
+fn render_bibliography(cfg: &CompilerConfig, state: &mut RenderState) -> String {
+    if state.citations.is_empty() {
+        return String::new();
+    }
+    let keys = std::mem::take(&mut state.citations);
+    let mut s = String::from("<section class=\"bibliography\">\n<h2>references</h2>\n<ol>");
+    for key in &keys {
+        let entry = parse_text(cfg.bibliography[key].clone());
+        let entry = entry.iter().map(|t| t.render(cfg, state)).collect::<String>();
+        s.push_str(&format!("<li id=\"{}cite-{}\">{}</li>", state.id_namespace, escape_html(key), entry));
+    }
+    s.push_str("</ol>\n</section>\n");
+    s
+}
+
+// per-document settings and state accumulated while rendering
+#[derive(Default)]
+struct RenderState {
+    id_namespace: String,                     // prefix for generated ids
+    slug: String,                             // the post's, for a per-post math cache
+    code_lang_map: BTreeMap<String, String>,  // from front matter
+    dir: Option<String>,                      // from front matter
+    citations: Vec<String>,     // bibliography keys, in order of first citation
+    equations: usize,           // display equations numbered so far
+    seen_paragraph: bool,       // the first paragraph is the lead
+    header_ids: BTreeSet<String>,  // slugs given out so far
+    headers: Vec<(usize, String, String)>,  // level, source, id; for the toc
+    figures: Vec<(usize, String, String)>,  // number, caption, id; for the lof
+}
+
+impl Text {
+    fn render(&self, cfg: &CompilerConfig, state: &mut RenderState) -> String {
+        let inner = self.render_unmarked(cfg, state);
+        self.marks.iter().rev().fold(inner, |s, mark| wrap_mark(mark, &s))
+    }
+
+    fn render_unmarked(&self, cfg: &CompilerConfig, state: &mut RenderState) -> String {
+        match self.fmt {
+            TextFormat::Plain if cfg.preserve_spaces => {
+                SPACE_RUN_REGEX.replace_all(&escape_html(&self.src), |caps: &regex::Captures| {
+                    "&nbsp;".repeat(caps[0].len())
+                }).to_string()
+            }
+            TextFormat::Plain => {
+                escape_html(&self.src)
+            }
+            TextFormat::Bold | TextFormat::Italic | TextFormat::Strikethrough => {
+                wrap_mark(&self.fmt, &escape_html(&self.src))
+            }
+            TextFormat::InlineMath => {
+                let svg = render_math(&self.src, cfg, false, &state.slug).unwrap_or_else(
+                    |e| render_math_error(&self.src, &e, false, cfg)
+                );
+                format!("<span class=\"inline-math\">{}</span>", svg)
+            }
+            TextFormat::InlineCode => {
+                format!("<span class=\"inline-code\">{}</span>", escape_html(&self.src))
+            }
+            TextFormat::Link(ref url, ref title) => {
+                let title = title.as_ref()
+                    .map(|t| format!(" title=\"{}\"", escape_html(t)))
+                    .unwrap_or_default();
+                format!("<a href=\"{}\"{}>{}</a>", escape_url(url), title, escape_html(&self.src))
+            }
+            TextFormat::Citation => {
+                if !cfg.bibliography.contains_key(&self.src) {
+                    warn(&format!("unknown citation key: {}", self.src));
+                    return format!("[@{}]", escape_html(&self.src));
+                }
+                let n = match state.citations.iter().position(|k| *k == self.src) {
+                    Some(i) => i + 1,
+                    None => {
+                        state.citations.push(self.src.clone());
+                        state.citations.len()
+                    }
+                };
+                format!(
+                    "<sup class=\"citation\"><a href=\"#{}cite-{}\">[{}]</a></sup>",
+                    state.id_namespace, escape_html(&self.src), n
+                )
+            }
+            TextFormat::FootnoteRef => {
+                format!(
+                    "<sup id=\"{ns}ref{id}\"><a href=\"#{ns}fn{id}\">[{id}]</a></sup>",
+                    ns = state.id_namespace, id = &self.src
+                )
+            }
+            TextFormat::FootnoteSeparator if cfg.footnote_ref_separator.is_empty() => String::new(),
+            TextFormat::FootnoteSeparator => {
+                format!("<sup>{}</sup>", escape_html(&cfg.footnote_ref_separator))
+            }
+            _ => {
+                String::clone(&self.src)
+            }
+        }
+    }
+}
+
+fn wrap_mark(mark: &TextFormat, html: &str) -> String {
+    match mark {
+        TextFormat::Bold => format!("<span class=\"bold\">{}</span>", html),
+        TextFormat::Italic => format!("<span class=\"italic\">{}</span>", html),
+        TextFormat::Strikethrough => format!("<del>{}</del>", html),
+        _ => html.to_string(),
+    }
+}
+
+impl Block {
+    fn render(&self, cfg: &CompilerConfig, state: &mut RenderState) -> String {
+        match self {
+            Block::Paragraph(chunks) => {
+                let c = chunks.iter().map(|text| text.render(cfg, state)).collect::<String>();
+                let is_lead = cfg.lead_paragraph && !state.seen_paragraph;
+                state.seen_paragraph = true;
+                if is_lead {
+                    format!("<p class=\"lead\">{}</p>\n", c)
+                } else {
+                    format!("<p>{}</p>\n", c)
+                }
+            },
+            Block::Header(level, src) => {
+                let tag = format!("h{}", (*level).clamp(1, 6));
+                let id = header_id(src, state);
+                state.headers.push((*level, src.clone(), id.clone()));
+                let mut s = format!(
+                    "<{} id=\"{}\">{} <a href=\"#{}\" class=\"header-anchor\">#</a></{}>\n",
+                    tag, id, render_header_text(src), id, tag
+                );
+                if *level == 1 {
+                    s.push_str("<hr><br>")
+                }
+                s
+            }
+            Block::Math(s) => {
+                let svg = render_math(s, cfg, true, &state.slug).unwrap_or_else(
+                    |e| render_math_error(s, &e, true, cfg)
+                );
+                // centered is the stylesheet's default
+                let class = if cfg.display_math_align == DisplayMathAlign::Left { "display-math left" } else { "display-math" };
+                let math = format!("<div class=\"{}\">{}</div>", class, svg);
+                if cfg.number_equations {
+                    state.equations += 1;
+                    format!(
+                        "<div class=\"equation\">{}<span class=\"equation-number\">({})</span></div>",
+                        math, state.equations
+                    )
+                } else {
+                    math
+                }
+            }
+            Block::Code(lang, src, caption) => {
+                let mut lang = resolve_code_lang(lang, state, cfg);
+                if lang.is_empty() && cfg.autodetect_code_lang {
+                    lang = detect_code_lang(src).unwrap_or_default().to_string();
+                }
+                // scrolling is the stylesheet's default
+                let wrap = if cfg.code_wrap == CodeWrap::Wrap { " class=\"code-wrap\"" } else { "" };
+                let mut pre = format!(
+                    "<pre{}><code class=\"code-{}\">{}</code></pre>",
+                    wrap, escape_html(&lang), render_code_src(src, &lang, cfg)
+                );
+                if cfg.show_code_lang && !lang.is_empty() {
+                    pre = format!("<span class=\"code-lang\">{}</span>{}", escape_html(&lang), pre);
+                }
+                if let Some(caption) = caption {
+                    pre = format!("<div class=\"code-caption\">{}</div>{}", escape_html(caption), pre);
+                }
+                if cfg.code_copy_button {
+                    format!("<div class=\"code-block\"><button class=\"copy\">Copy</button>{}</div>", pre)
+                } else {
+                    pre
+                }
+            }
+            Block::Image(alt, url, width, unit) => {
+                let full_src = escape_url(&image_src(url, cfg));
+                let thumb = cfg.image_thumbnails.and_then(|max_dim| ensure_thumbnail(url, max_dim, cfg));
+                let shown = thumb.as_deref().unwrap_or(url);
+                let path_str = &thumb.as_deref().map(|t| escape_url(&image_src(t, cfg)))
+                    .unwrap_or_else(|| full_src.clone());
+                let mut img = format!("<img src=\"{}\" alt=\"{}\" class=\"image\"", path_str, escape_html(alt));
+                // intrinsic size up front avoids layout shift while loading
+                if let Some((w, h)) = image_dimensions(shown, cfg) {
+                    // only % and px can be turned into pixels here; other units just keep the aspect ratio.
+                    // in u64 so a large image at a large width can't overflow
+                    let (w, h, width) = (u64::from(w), u64::from(h), u64::from(*width));
+                    let (w, h) = match unit.as_str() {
+                        "%" => (w * width / 100, h * width / 100),
+                        "px" if w > 0 => (width, h * width / w),
+                        _ => (w, h),
+                    };
+                    img.push_str(&format!(" width=\"{}\" height=\"{}\"", w, h));
+                }
+                if *width != 100 || unit != "%" {
+                    img.push_str(&format!(" style=\"width: {}{};\"", width, unit));
+                }
+                img.push('>');
+                // thumbnails link through to the full-size image
+                let img = if thumb.is_some() {
+                    format!("<a href=\"{}\">{}</a>", full_src, img)
+                } else {
+                    img
+                };
+                // the alt text doubles as the caption; uncaptioned images aren't numbered
+                if cfg.number_figures && !alt.trim().is_empty() {
+                    let n = state.figures.len() + 1;
+                    let id = format!("{}fig-{}", state.id_namespace, n);
+                    let caption = escape_html(alt);
+                    let figure = format!(
+                        "<figure id=\"{}\" class=\"figure\">{}<figcaption>Figure {}: {}</figcaption></figure>",
+                        id, img, n, caption
+                    );
+                    state.figures.push((n, caption, id));
+                    figure
+                } else {
+                    img
+                }
+            }
+            Block::Html(src) => {
+                src.to_string()
+            }
+            // only meaningful to render_post_body
+            Block::FootnoteFlush => String::new(),
+            Block::Toc => TOC_PLACEHOLDER.to_string(),
+            Block::Lof => LOF_PLACEHOLDER.to_string(),
+            Block::Container{name, content} if name == "tabs" => {
+                render_code_tabs(content, cfg, state)
+            }
+            Block::Container{name, content} => {
+                let (tag, class) = match cfg.containers.get(name) {
+                    Some(spec) => (spec.tag.as_str(), spec.class.as_str()),
+                    None => {
+                        warn(&format!("unknown container '{}', using a div", name));
+                        ("div", name.as_str())
+                    }
+                };
+                let inner = content.iter().map(|block| block.render(cfg, state)).collect::<String>();
+                format!("<{} class=\"{}\">\n{}</{}>\n", tag, escape_html(class), inner, tag)
+            }
+            Block::DefinitionList(items) => {
+                let mut s = String::from("<dl>\n");
+                for item in items {
+                    for term in &item.terms {
+                        let t = term.iter().map(|text| text.render(cfg, state)).collect::<String>();
+                        s.push_str(&format!("<dt>{}</dt>\n", t));
+                    }
+                    for definition in &item.definitions {
+                        let d = definition.iter().map(|text| text.render(cfg, state)).collect::<String>();
+                        s.push_str(&format!("<dd>{}</dd>\n", d));
+                    }
+                }
+                s.push_str("</dl>\n");
+                s
+            }
+            Block::Table{headers, rows, align} => {
+                let mut render_row = |cells: &[Vec<Text>], tag: &str| {
+                    let mut s = String::from("<tr>");
+                    for (i, cell) in cells.iter().enumerate() {
+                        let style = match align.get(i) {
+                            Some(Alignment::Left) => " style=\"text-align: left;\"",
+                            Some(Alignment::Center) => " style=\"text-align: center;\"",
+                            Some(Alignment::Right) => " style=\"text-align: right;\"",
+                            _ => "",
+                        };
+                        let c = cell.iter().map(|text| text.render(cfg, state)).collect::<String>();
+                        s.push_str(&format!("<{}{}>{}</{}>", tag, style, c, tag));
+                    }
+                    s.push_str("</tr>\n");
+                    s
+                };
+                let mut s = String::from("<table>\n<thead>\n");
+                s.push_str(&render_row(headers, "th"));
+                s.push_str("</thead>\n<tbody>\n");
+                for row in rows {
+                    s.push_str(&render_row(row, "td"));
+                }
+                s.push_str("</tbody>\n</table>\n");
+                s
+            }
+            Block::Quote(lines) => {
+                // open/close just enough tags to move between depths
+                let mut s = String::new();
+                let mut depth = 0;
+                for (level, texts) in lines {
+                    if *level > depth {
+                        s.push_str(&"<blockquote>".repeat(level - depth));
+                    } else {
+                        s.push_str(&"</blockquote>".repeat(depth - level));
+                    }
+                    depth = *level;
+                    let c = texts.iter().map(|text| text.render(cfg, state)).collect::<String>();
+                    s.push_str(&format!("<p class=quote>{}</p>", c));
+                }
+                s.push_str(&"</blockquote>".repeat(depth));
+                s.push('\n');
+                s
+            }
+            Block::Footnote(id, chunks) => {
+                let c = chunks.iter().map(|text| text.render(cfg, state)).collect::<String>();
+                let ns = &state.id_namespace;
+                let mut s = format!("<p id=\"{}fn{}\">", ns, id);
+                if cfg.footnote_id_prefix {
+                    s.push_str(&format!("<a href=\"#{}ref{}\">[{}]</a> ", ns, id, id));
+                }
+                s.push_str(&c);
+                if !cfg.footnote_backref_symbol.is_empty() {
+                    s.push_str(&format!(
+                        " <a href=\"#{}ref{}\" class=\"footnote-backref\">{}</a>",
+                        ns, id, escape_html(&cfg.footnote_backref_symbol)
+                    ));
+                }
+                s.push_str("</p>");
+                s
+            }
+            Block::List(is_ordered, list) => {
+                let mut s = String::new();
+                let mut current_level = 0;
+                let tag = if *is_ordered { "ol" } else { "ul" };
+                // `1)` markers keep their paren, see .paren-delim
+                let class = if *is_ordered && list.first().is_some_and(|item| item.delimiter == ')') {
+                    " class=\"paren-delim\""
+                } else {
+                    ""
+                };
+                
+                // Start first list; nested lists inherit its direction
+                match &state.dir {
+                    Some(dir) => s.push_str(&format!("<{}{} dir=\"{}\">", tag, class, dir)),
+                    None => s.push_str(&format!("<{}{}>", tag, class)),
+                }
+                
+                for (i, item) in list.iter().enumerate() {
+                    let mut inner_text = item.content.iter().map(|t| t.render(cfg, state)).collect::<String>();
+                    for block in &item.blocks {
+                        inner_text.push_str(&block.render(cfg, state));
+                    }
+                    
+                    // Handle level changes
+                    if item.level > current_level {
+                        // Open nested lists (don't close the previous <li> yet)
+                        for _ in current_level..item.level {
+                            s.push_str(&format!("<{}>", tag));
+                        }
+                    } else if item.level < current_level {
+                        // Close nested lists and previous <li>
+                        s.push_str("</li>");
+                        for _ in item.level..current_level {
+                            s.push_str(&format!("</{}>", tag));
+                            s.push_str("</li>");
+                        }
+                    } else if i > 0 {
+                        // Same level, close previous <li>
+                        s.push_str("</li>");
+                    }
+                    
+                    // Add current item
+                    s.push_str(&format!("<li>{}", inner_text));
+                    current_level = item.level;
+                }
+                
+                // Close remaining tags
+                s.push_str("</li>");
+                for _ in 0..current_level {
+                    s.push_str(&format!("</{}>", tag));
+                    s.push_str("</li>");
+                }
+                s.push_str(&format!("</{}>", tag));
+                
+                s
+            }
+        }
+    }
+}
+
+// counts telltale snippets per language; a guess needs two hits and a clear lead
+fn detect_code_lang(src: &str) -> Option<&'static str> {
+    const MARKERS: &[(&str, &[&str])] = &[
+        ("rust", &["fn ", "let mut ", "impl ", "pub fn", "println!", "&mut ", "use std::", "-> ", "::new("]),
+        ("python", &["def ", "import ", "self.", "elif ", "print(", "None", "__init__", "):\n"]),
+        ("javascript", &["function ", "const ", "=> ", "console.log", "===", "require(", "document."]),
+        ("c", &["#include", "int main", "printf(", "void ", "malloc(", "NULL"]),
+        ("bash", &["#!/bin/", "echo ", "$(", "\nfi", "then\n", "export ", "sudo "]),
+        ("html", &["<div", "<html", "<p>", "</a>", "<span", "<!DOCTYPE"]),
+    ];
+    let mut scores = MARKERS.iter()
+        .map(|(lang, markers)| (markers.iter().filter(|m| src.contains(*m)).count(), *lang))
+        .collect::<Vec<_>>();
+    scores.sort_by_key(|&(score, _)| std::cmp::Reverse(score));
+    match scores.as_slice() {
+        [(best, lang), (next, _), ..] if *best >= 2 && best > next => Some(lang),
+        _ => None,
+    }
+}
+
+// escaped source, or highlighted spans when enabled and the language is known
+fn render_code_src(src: &str, lang: &str, cfg: &CompilerConfig) -> String {
+    if cfg.syntax_highlighting
+        && let Some(html) = highlight_code(src, lang) {
+        return html;
+    }
+    escape_html(src)
+}
+
+// inline-styled spans; syntect escapes the source itself
+#[cfg(feature = "highlight")]
+fn highlight_code(src: &str, lang: &str) -> Option<String> {
+    use syntect::easy::HighlightLines;
+    use syntect::highlighting::ThemeSet;
+    use syntect::html::{IncludeBackground, styled_line_to_highlighted_html};
+    use syntect::parsing::SyntaxSet;
+    use syntect::util::LinesWithEndings;
+    static SYNTAXES: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+    static THEMES: LazyLock<ThemeSet> = LazyLock::new(ThemeSet::load_defaults);
+
+    if lang.is_empty() {
+        return None;
+    }
+    let syntax = SYNTAXES.find_syntax_by_token(lang)?;
+    let mut highlighter = HighlightLines::new(syntax, &THEMES.themes["InspiredGitHub"]);
+    let mut html = String::new();
+    for line in LinesWithEndings::from(src) {
+        let regions = highlighter.highlight_line(line, &SYNTAXES).ok()?;
+        html.push_str(&styled_line_to_highlighted_html(&regions, IncludeBackground::No).ok()?);
+    }
+    Some(html)
+}
+
+#[cfg(not(feature = "highlight"))]
+fn highlight_code(_src: &str, _lang: &str) -> Option<String> {
+    static WARNED: OnceLock<()> = OnceLock::new();
+    WARNED.get_or_init(|| warn("syntax_highlighting needs a build with --features highlight"));
+    None
+}
+
+// headers aren't parsed for inline markup, so escape them, keeping `code` spans as code
+fn render_header_text(src: &str) -> String {
+    let mut s = String::new();
+    let mut last = 0;
+    for caps in HEADER_CODE_REGEX.captures_iter(src) {
+        let m = caps.get(0).unwrap();
+        s.push_str(&escape_html(&src[last..m.start()]));
+        s.push_str(&format!("<span class=\"inline-code\">{}</span>", escape_html(&caps[1])));
+        last = m.end();
+    }
+    s.push_str(&escape_html(&src[last..]));
+    s
+}
+
+// namespaced slug, suffixed -1, -2, ... when an earlier header already took it
+fn header_id(src: &str, state: &mut RenderState) -> String {
+    let mut slug = slugify(src);
+    if slug.is_empty() {
+        slug = "section".to_string();
+    }
+    let mut id = slug.clone();
+    let mut n = 0;
+    while state.header_ids.contains(&id) {
+        n += 1;
+        id = format!("{}-{}", slug, n);
+    }
+    state.header_ids.insert(id.clone());
+    format!("{}{}", state.id_namespace, id)
+}
+
+// per-post overrides first, then the global aliases
+fn resolve_code_lang(lang: &str, state: &RenderState, cfg: &CompilerConfig) -> String {
+    let lang = state.code_lang_map.get(lang)
+        .or_else(|| state.code_lang_map.get("*"))
+        .map(String::as_str)
+        .unwrap_or(lang);
+    cfg.code_lang_aliases.get(lang).cloned().unwrap_or_else(|| lang.to_string())
+}
+
+// writes `name.thumb.ext` next to a local image whose longest edge exceeds max_dim,
+// returning its url; regenerated only when the original is newer
+fn ensure_thumbnail(url: &str, max_dim: u32, cfg: &CompilerConfig) -> Option<String> {
+    if url.starts_with('/') || url.contains("://") {
+        return None;
+    }
+    let src_path = cfg.images_dir.join(url);
+    let (w, h) = match read_image(&src_path).and_then(|r| r.into_dimensions()) {
+        Ok(dims) => dims,
+        Err(e) => {
+            warn(&format!("could not read image {}: {}", src_path.display(), e));
+            return None;
+        }
+    };
+    if w.max(h) <= max_dim {
+        return None;
+    }
+
+    let url_path = Path::new(url);
+    let stem = url_path.file_stem()?.to_str()?;
+    let ext = url_path.extension().and_then(|e| e.to_str()).unwrap_or("png");
+    let thumb_url = match url.rfind('/') {
+        Some(i) => format!("{}/{}.thumb.{}", &url[..i], stem, ext),
+        None => format!("{}.thumb.{}", stem, ext),
+    };
+    let thumb_path = cfg.images_dir.join(&thumb_url);
+
+    let modified = |p: &Path| std::fs::metadata(p).and_then(|m| m.modified()).ok();
+    if modified(&thumb_path).is_none() || modified(&thumb_path) < modified(&src_path) {
+        let result = read_image(&src_path)
+            .and_then(|r| r.decode())
+            .and_then(|img| img.thumbnail(max_dim, max_dim).save(&thumb_path));
+        if let Err(e) = result {
+            warn(&format!("could not write thumbnail {}: {}", thumb_path.display(), e));
+            return None;
+        }
+        debug!("\twrote thumbnail: {}", thumb_path.display());
+    }
+    Some(thumb_url)
+}
+
+fn image_dimensions(url: &str, cfg: &CompilerConfig) -> Option<(u32, u32)> {
+    if url.starts_with('/') || url.contains("://") {
+        return None;
+    }
+    let path = cfg.images_dir.join(url);
+    if !path.exists() {
+        return None;  // already reported by image_src
+    }
+    match read_image(&path).and_then(|r| r.into_dimensions()) {
+        Ok(dims) => Some(dims),
+        Err(e) => {
+            warn(&format!("could not read image size of {}: {}", path.display(), e));
+            None
+        }
+    }
+}
+
+// sniffs the format rather than trusting the extension
+fn read_image(path: &Path) -> image::ImageResult<image::ImageReader<std::io::BufReader<std::fs::File>>> {
+    Ok(image::ImageReader::open(path)?.with_guessed_format()?)
+}
+
+// for text going into element content or attribute values
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// bare `&` (query strings) => `&amp;`, leaving existing entities alone; quotes and angle
+// brackets can't end the attribute
+fn escape_url(url: &str) -> String {
+    let mut s = String::with_capacity(url.len());
+    for (i, c) in url.char_indices() {
+        match c {
+            '&' if !ENTITY_REGEX.is_match(&url[i..]) => s.push_str("&amp;"),
+            '"' => s.push_str("&quot;"),
+            '<' => s.push_str("&lt;"),
+            '>' => s.push_str("&gt;"),
+            _ => s.push(c),
+        }
+    }
+    s
+}
+
+// images_url is a url prefix, not a filesystem path, so join with '/' regardless of OS
+fn image_src(url: &str, cfg: &CompilerConfig) -> String {
+    if url.starts_with('/') || url.contains("://") {
+        return url.to_string();
+    }
+    if !cfg.images_dir.join(url).exists() {
+        warn(&format!("image not found: {}", cfg.images_dir.join(url).display()));
+    }
+    format!("{}/{}", cfg.images_url.trim_end_matches('/'), url)
+}
+
+// the first `max_words` words of the leading paragraphs, cut on a word boundary.
+// built from Texts rather than by slicing html, so every tag it opens gets closed
+fn excerpt(blocks: &[Block], max_words: usize, slug: &str, cfg: &CompilerConfig) -> String {
+    let mut texts = Vec::new();
+    let mut words = 0;
+    let mut truncated = false;
+    'blocks: for block in blocks {
+        let Block::Paragraph(para) = block else { continue };
+        if !texts.is_empty() {
+            texts.push(Text{src: " ".to_string(), fmt: TextFormat::Plain, marks: Vec::new()});
+        }
+        for text in para {
+            let (src, fmt) = match &text.fmt {
+                // refs would point at (and duplicate) ids elsewhere on the page
+                TextFormat::FootnoteRef | TextFormat::FootnoteSeparator | TextFormat::Citation => continue,
+                // no latex just for a preview
+                TextFormat::InlineMath => (format!("${}$", text.src), TextFormat::Plain),
+                fmt => (text.src.clone(), fmt.clone()),
+            };
+            let n = src.split_whitespace().count();
+            if words + n <= max_words {
+                words += n;
+                texts.push(Text{src, fmt, marks: text.marks.clone()});
+                continue;
+            }
+            let cut = nth_word_end(&src, max_words - words);
+            if cut > 0 {
+                texts.push(Text{src: src[..cut].to_string(), fmt, marks: text.marks.clone()});
+            }
+            truncated = true;
+            break 'blocks;
+        }
+    }
+
+    let state = &mut RenderState { slug: slug.to_string(), ..Default::default() };
+    let mut s = texts.iter().map(|t| t.render(cfg, state)).collect::<String>();
+    if truncated {
+        s = s.trim_end().to_string();
+        s.push('…');
+    }
+    s
+}
+
+// byte offset just past the nth word
+fn nth_word_end(src: &str, n: usize) -> usize {
+    if n == 0 {
+        return 0;
+    }
+    let mut count = 0;
+    let mut in_word = false;
+    for (i, c) in src.char_indices() {
+        if c.is_whitespace() {
+            if in_word {
+                count += 1;
+                if count == n {
+                    return i;
+                }
+            }
+            in_word = false;
+        } else {
+            in_word = true;
+        }
+    }
+    src.len()
+}
+
+
+/* ========================================
+                 plain text
+   ======================================== */
+fn render_plain_text(blocks: &[Block]) -> String {
+    blocks.iter()
+        .map(|block| block.to_plain_text())
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n") + "\n"
+}
+
+fn plain_text(texts: &[Text]) -> String {
+    texts.iter().map(|t| t.to_plain_text()).collect::<String>().trim().to_string()
+}
+
+impl Text {
+    fn to_plain_text(&self) -> String {
+        match self.fmt {
+            TextFormat::Link(ref url, _) => format!("{} ({})", self.src, url),
+            TextFormat::InlineMath => format!("${}$", self.src),
+            TextFormat::FootnoteRef => format!("[{}]", self.src),
+            TextFormat::Citation => format!("[@{}]", self.src),
+            _ => self.src.clone(),
+        }
+    }
+}
+
+impl Block {
+    fn to_plain_text(&self) -> String {
+        match self {
+            Block::Paragraph(texts) => plain_text(texts),
+            Block::Header(level, src) => {
+                let underline = if *level == 1 { "=" } else { "-" };
+                format!("{}\n{}", src, underline.repeat(src.chars().count()))
+            }
+            Block::Code(_, src, _) => src.trim_end().to_string(),
+            Block::Math(src) => src.trim_end().to_string(),
+            Block::Image(alt, url, _, _) => format!("[image: {}] ({})", alt, url),
+            Block::Html(_) | Block::FootnoteFlush | Block::Toc | Block::Lof => String::new(),
+            Block::Container{content, ..} => {
+                content.iter().map(|b| b.to_plain_text()).collect::<Vec<_>>().join("\n\n")
+            }
+            Block::DefinitionList(items) => {
+                items.iter()
+                    .map(|item| {
+                        let mut lines = item.terms.iter().map(|t| plain_text(t)).collect::<Vec<_>>();
+                        lines.extend(item.definitions.iter().map(|d| format!("    {}", plain_text(d))));
+                        lines.join("\n")
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+            Block::Table{headers, rows, ..} => {
+                std::iter::once(headers).chain(rows)
+                    .map(|cells| cells.iter().map(|c| plain_text(c)).collect::<Vec<_>>().join(" | "))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+            Block::Quote(lines) => {
+                lines.iter()
+                    .map(|(depth, texts)| format!("{}{}", "> ".repeat(*depth), plain_text(texts)))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+            Block::Footnote(id, texts) => format!("[{}] {}", id, plain_text(texts)),
+            Block::List(_, items) => {
+                items.iter()
+                    .map(|item| {
+                        let mut s = format!("{}- {}", "  ".repeat(item.level), plain_text(&item.content));
+                        for block in &item.blocks {
+                            s.push('\n');
+                            s.push_str(&block.to_plain_text());
+                        }
+                        s
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+        }
+    }
+}
+
+
+/* ========================================
+                    math
+   ======================================== */
+fn render_math(math: &str,
+    cfg: &CompilerConfig, is_display: bool, slug: &str) -> Result<String, MinissgError> {
+    if cfg.math_backend == MathBackend::Off {
+        return Ok(render_math_source(math));
+    }
+    if cfg.math_backend == MathBackend::Client {
+        return Ok(render_math_for_client(math, cfg, is_display, slug));
+    }
+    if cfg.math_backend == MathBackend::MathMl {
+        match render_math_to_mathml(math, is_display, cfg) {
+            Ok(mathml) => return Ok(mathml),
+            Err(e) => warn(&format!("{}; falling back to svg", e)),
+        }
+    }
+    render_math_to_svg(math, cfg, is_display, slug)
+}
+
+// without a tex install the formula itself is the most useful thing to show
+fn render_math_source(math: &str) -> String {
+    format!("<code class=\"math-source\">{}</code>", escape_html(math))
+}
+
+fn render_math_error(math: &str, err: &MinissgError, is_display: bool, cfg: &CompilerConfig) -> String {
+    // not every failure is warned about (a missing latex only once), but none may be cached
+    RENDER_ISSUES.set(RENDER_ISSUES.get() + 1);
+    if let MinissgError::ToolMissing(_) = err {
+        render_math_source(math)
+    } else if cfg.math_error_display == MathErrorDisplay::Source {
+        let (open, close) = if is_display { ("\\[", "\\]") } else { ("$", "$") };
+        let tex = escape_html(math);
+        format!("<span class=\"latex-source\">{}{}{}</span>", open, tex, close)
+    } else if cfg.math_error_display == MathErrorDisplay::Hidden {
+        String::new()
+    } else {
+        format!("<code class='latex-error'>{}</code>", escape_html(&err.to_string()))
+    }
+}
+
+fn is_math_environment(math: &str) -> bool {
+    math.trim_start().starts_with("\\begin{")
+}
+
+fn render_math_for_client(math: &str, cfg: &CompilerConfig, is_display: bool, slug: &str) -> String {
+    let (open, close) = if is_display && is_math_environment(math) { ("", "") }
+        else if is_display { ("\\[", "\\]") }
+        else { ("\\(", "\\)") };
+    let tex = escape_html(math);
+    let mut s = format!("<span class=\"math-client\">{}{}{}</span>", open, tex, close);
+
+    // progressive enhancement: no-js readers get the pre-rendered svg
+    if cfg.math_noscript_fallback {
+        match render_math_to_svg(math, cfg, is_display, slug) {
+            Ok(svg) => s.push_str(&format!("<noscript>{}</noscript>", svg)),
+            Err(e) => warn(&format!("no noscript fallback for '{}': {}", math, e)),
+        }
+    }
+    s
+}
+
+fn render_math_to_mathml(math: &str, is_display: bool, cfg: &CompilerConfig) -> Result<String, MinissgError> {
+    let output = run_with_timeout(
+        Command::new("latexmlmath").args(["--quiet", "--pmml=-", "--", math]),
+        cfg.math_timeout,
+    )?;
+
+    let mathml = String::from_utf8_lossy(&output.stdout);
+    let start = mathml.find("<math").filter(|_| output.status.success())
+        .ok_or_else(|| MinissgError::Math(format!("latexmlmath failed on: {}", math)))?;
+
+    debug!("\tcompiling MathML expr: {}... OK", math.replace("\n", " "));
+    let mathml = mathml[start..].trim_end().to_string();
+    if is_display {
+        Ok(mathml.replacen("<math", "<math display=\"block\"", 1))
+    } else {
+        Ok(mathml)
+    }
+}
+
+fn render_math_to_svg(math: &str, 
+    cfg: &CompilerConfig, is_display: bool, slug: &str) -> Result<String, MinissgError> {
+    let cache_path = math_cache_path(math, cfg, is_display, slug);
+
+    // whoever gets here second waits, then finds the first one's svg on disk
+    let entry = MATH_IN_FLIGHT.lock().unwrap().entry(cache_path.clone()).or_default().clone();
+    let svg = {
+        let _compiling = entry.lock().unwrap();
+        std::fs::read_to_string(&cache_path).or_else(|_| {
+            compile_math_to_svg(math, cfg, is_display).inspect(|svg| write_cache(&cache_path, svg))
+        })
+    };
+    // the last one out removes the lock, so a long watch session doesn't pile them up. clones
+    // are only taken with the map locked, so nobody can pick this one up meanwhile
+    let mut in_flight = MATH_IN_FLIGHT.lock().unwrap();
+    if Arc::strong_count(&entry) == 2 {
+        in_flight.remove(&cache_path);
+    }
+    drop(in_flight);
+    let mut svg = svg?;
+    if cfg.optimize_svg {
+        svg = optimize_svg(&svg);
+    }
+    if let Some(class) = &cfg.math_svg_class {
+        svg = svg.replacen("<svg", &format!("<svg class=\"{}\"", escape_html(class)), 1);
+    }
+    Ok(svg)
+}
+
+// keyed on everything that reaches latex, so a template edit invalidates it
+fn math_cache_path(math: &str, cfg: &CompilerConfig, is_display: bool, slug: &str) -> PathBuf {
+    let mut hasher = StableHasher::default();
+    hasher.field(math.as_bytes());
+    hasher.field(&[u8::from(is_display)]);
+    hasher.field(cfg.math_template.as_bytes());
+    hasher.field(cfg.math_font_package.as_deref().unwrap_or("").as_bytes());
+    let mut cache_dir = cfg.cache_dir.join("math");
+    if cfg.math_cache_scope == MathCacheScope::PerPost {
+        cache_dir.push(slug);
+    }
+    cache_dir
+        .join(format!("{:016x}", hasher.finish()))
+        .with_extension("svg")
+}
+
+// raw dvisvgm output for one expression
+fn compile_math_to_svg(math: &str, cfg: &CompilerConfig, is_display: bool) -> Result<String, MinissgError> {
+    let temp_dir = tempfile::tempdir()
+        .map_err(|e| MinissgError::Math(format!("could not create temp dir: {}", e)))?;
+    let tex_path = temp_dir.path().join("math.tex");
+    std::fs::write(&tex_path, math_tex(math, cfg, is_display))
+        .map_err(|e| MinissgError::Math(format!("could not write {}: {}", tex_path.display(), e)))?;
+    
+    let latex_output = run_with_timeout(
+        Command::new("latex")
+            .args(["-interaction=nonstopmode", "-halt-on-error", "-output-directory"])
+            .arg(temp_dir.path())
+            .arg(&tex_path),
+        cfg.math_timeout,
+    ).inspect_err(|e| {
+        // check_math_tooling already said latex is missing, once for the whole build
+        if !matches!(e, MinissgError::ToolMissing(_)) {
+            warn(&format!("compiling TeX expr: {}... ERR: {}", math, e));
+        }
+    })?;
+
+    if !latex_output.status.success() {
+        let err = String::from_utf8_lossy(&latex_output.stdout);
+        warn(&format!("compiling TeX expr: {}... ERR:\n{}", math, err));
+        // the `! ...` line names the problem, e.g. a missing .sty for math_font_package
+        let summary = err.lines().find(|l| l.starts_with("! ")).unwrap_or(&err);
+        return Err(MinissgError::Math(format!("LaTeX failed: {}", summary)));
+    }
+    
+    debug!("\tcompiling TeX expr: {}... OK", math.replace("\n", " "));
+    let dvi_path = temp_dir.path().join("math.dvi");
+    
+    if !dvi_path.exists() {
+        return Err(MinissgError::Math(format!("DVI file not found at {:?}", dvi_path)));
+    }
+    
+    let svg_output = run_with_timeout(
+        Command::new("dvisvgm").args(["--no-fonts", "--exact", "--stdout"]).arg(&dvi_path),
+        cfg.math_timeout,
+    )?;
+    // checked before anything is cached, an empty svg would stick around for good
+    if !svg_output.status.success() || svg_output.stdout.is_empty() {
+        let err = String::from_utf8_lossy(&svg_output.stderr);
+        return Err(MinissgError::Math(format!("dvisvgm failed: {}", err.trim())));
+    }
+    
+    Ok(String::from_utf8_lossy(&svg_output.stdout).to_string())
+}
+
+// the whole .tex file for one expression
+fn math_tex(math: &str, cfg: &CompilerConfig, is_display: bool) -> String {
+    // `align` and friends are display math already; wrapping them again breaks latex
+    let inner_contents = 
+        if is_display && is_math_environment(math) { math.to_string() }
+        else if is_display { format!("\\[{}\\]", math) } 
+        else { format!("${}$", math) };
+    math_template(cfg).replace("{{content}}", &inner_contents)
+}
+
+// the math template with math_font_package added to its preamble
+fn math_template(cfg: &CompilerConfig) -> String {
+    match &cfg.math_font_package {
+        Some(package) => cfg.math_template.replacen(
+            "\\begin{document}",
+            &format!("\\usepackage{{{}}}\n\\begin{{document}}", package),
+            1,
+        ),
+        None => cfg.math_template.clone(),
+    }
+}
+
+// like Command::output, but kills the child if it runs past `timeout`
+fn run_with_timeout(cmd: &mut Command, timeout: Duration) -> Result<Output, MinissgError> {
+    let name = cmd.get_program().to_string_lossy().to_string();
+    let failed = |message: String| MinissgError::Command{program: name.clone(), message};
+    let mut child = cmd.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => MinissgError::ToolMissing(name.clone()),
+            _ => failed(e.to_string()),
+        })?;
+
+    // drain the pipes as we go so a chatty child can't block on a full buffer
+    let drain = |pipe: Option<Box<dyn Read + Send>>| std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+        buf
+    });
+    let stdout = drain(child.stdout.take().map(|p| Box::new(p) as Box<dyn Read + Send>));
+    let stderr = drain(child.stderr.take().map(|p| Box::new(p) as Box<dyn Read + Send>));
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(10)),
+            Ok(None) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(failed(format!("timed out after {:?}", timeout)));
+            }
+            Err(e) => return Err(failed(e.to_string())),
+        }
+    };
+    Ok(Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+// strips comments/metadata, trims coordinates to 3 decimals, drops inter-tag whitespace
+fn optimize_svg(svg: &str) -> String {
+    let svg = SVG_JUNK_REGEX.replace_all(svg, "");
+    let svg = SVG_DECIMAL_REGEX.replace_all(&svg, "$1");
+    let svg = SVG_GAP_REGEX.replace_all(&svg, "><");
+    svg.trim().to_string()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(src: &str, cfg: &CompilerConfig) -> String {
+        render_document(parse(src, cfg), cfg)
+    }
+
+    // what f returns, and how many warnings/errors it reported on this thread
+    fn issues<T>(f: impl FnOnce() -> T) -> (T, usize) {
+        let before = RENDER_ISSUES.get();
+        let out = f();
+        (out, RENDER_ISSUES.get() - before)
+    }
+
+    // stands in for latex + dvisvgm, which the tests can't count on
+    fn seed_math_cache(math: &str, is_display: bool, svg: &str, cfg: &CompilerConfig) {
+        write_cache(&math_cache_path(math, cfg, is_display, ""), svg);
+    }
+
+    // a config that reads and writes everything under dir
+    fn site_config(dir: &Path) -> CompilerConfig {
+        CompilerConfig {
+            posts_dir: dir.join("posts"),
+            images_dir: dir.join("images"),
+            output_dir: dir.join("www"),
+            cache_dir: dir.join("cache"),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn empty_link_url_renders_as_text() {
+        let (html, n) = issues(|| render("see [the docs]() here", &CompilerConfig::default()));
+        assert_eq!(html, "<p>see the docs here</p>\n");
+        assert_eq!(n, 1);
+    }
+
+    #[test]
+    fn empty_image_source_is_skipped() {
+        let cfg = CompilerConfig::default();
+        let (blocks, n) = issues(|| parse("![a picture]()", &cfg));
+        assert!(blocks.is_empty());
+        assert_eq!(n, 1);
+    }
+
+    #[test]
+    fn cached_post_is_restored_until_the_template_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cfg = site_config(dir.path());
+        let post = dir.path().join("post.md");
+        let out = cfg.output_dir.join("post.html");
+        std::fs::write(&post, "hello").unwrap();
+        compile_post(&post, &out, &cfg).unwrap();
+        let cached = std::fs::read_dir(cfg.cache_dir.join("posts")).unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect::<Vec<_>>();
+        assert_eq!(cached.len(), 1);
+
+        // a restored post is whatever the cache holds
+        std::fs::write(&cached[0], "from the cache").unwrap();
+        std::fs::remove_file(&out).unwrap();
+        compile_post(&post, &out, &cfg).unwrap();
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "from the cache");
+
+        cfg.templates.post = "<main>{{content}}</main>".to_string();
+        compile_post(&post, &out, &cfg).unwrap();
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "<main><p>hello</p>\n</main>");
+    }
+
+    #[test]
+    fn optimized_svg_is_smaller_with_the_same_elements() {
+        let svg = "<?xml version='1.0' encoding='UTF-8'?>\n<!-- This file was generated by dvisvgm -->\n\
+            <svg version='1.1' width='10.123456pt' height='5.5pt'>\n<metadata>dvisvgm</metadata>\n\
+            <g id='page1'>\n  <path d='M1.234567 2.5L3.1415926 4'/>\n</g>\n</svg>\n";
+        let optimized = optimize_svg(svg);
+        assert_eq!(optimized, "<svg version='1.1' width='10.123pt' height='5.5pt'>\
+            <g id='page1'><path d='M1.234 2.5L3.141 4'/></g></svg>");
+        assert!(optimized.len() < svg.len());
+    }
+
+    #[test]
+    fn collapsible_sections_wrap_each_h2_section() {
+        let cfg = CompilerConfig { collapsible_sections: true, ..Default::default() };
+        let html = render("## Usage\n\nrun it\n\n## Notes\n\nnone", &cfg);
+        assert_eq!(html, "<details open><summary><h2 id=\"usage\">Usage <a href=\"#usage\" class=\"header-anchor\">#</a></h2></summary>\n\
+            <p>run it</p>\n</details>\n\
+            <details open><summary><h2 id=\"notes\">Notes <a href=\"#notes\" class=\"header-anchor\">#</a></h2></summary>\n\
+            <p>none</p>\n</details>\n");
+    }
+
+    #[test]
+    fn deep_list_items_are_clamped_to_max_depth() {
+        let cfg = CompilerConfig { max_list_depth: 3, ..Default::default() };
+        let src = (0..10).map(|level| format!("{}- item {}\n", " ".repeat(level * 4), level)).collect::<String>();
+        let (blocks, n) = issues(|| parse(&src, &cfg));
+        let [Block::List(false, items)] = blocks.as_slice() else { panic!("not one list: {:?}", blocks) };
+        let levels = items.iter().map(|item| item.level).collect::<Vec<_>>();
+        assert_eq!(levels, [0, 1, 2, 3, 3, 3, 3, 3, 3, 3]);
+        assert_eq!(n, 6);
+    }
+
+    #[test]
+    fn copy_button_wraps_code_blocks() {
+        let cfg = CompilerConfig { code_copy_button: true, ..Default::default() };
+        assert_eq!(render("```rust\nfn main() {}\n```", &cfg),
+            "<div class=\"code-block\"><button class=\"copy\">Copy</button>\
+            <pre><code class=\"code-rust\">fn main() {}\n</code></pre></div>");
+    }
+
+    #[test]
+    fn image_src_joins_images_url_as_a_url() {
+        let cfg = CompilerConfig::default();
+        assert_eq!(image_src("foo.png", &cfg), "/static/images/foo.png");
+        assert_eq!(image_src("post/foo.png", &cfg), "/static/images/post/foo.png");
+        assert_eq!(image_src("/elsewhere/foo.png", &cfg), "/elsewhere/foo.png");
+        let cfg = CompilerConfig { images_url: "https://cdn.example.com/img/".to_string(), ..Default::default() };
+        assert_eq!(image_src("foo.png", &cfg), "https://cdn.example.com/img/foo.png");
+    }
+
+    #[test]
+    #[ignore = "needs latexmlmath"]
+    fn mathml_backend_renders_a_math_element() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = CompilerConfig { math_backend: MathBackend::MathMl, ..site_config(dir.path()) };
+        let (html, n) = issues(|| render("$x^2$", &cfg));
+        assert!(html.starts_with("<p><span class=\"inline-math\"><math"), "{}", html);
+        assert_eq!(n, 0);
+    }
+
+    #[test]
+    fn toml_front_matter_is_parsed() {
+        let src = "+++\ntitle = \"Hello\"\ntags = [\"rust\", \"web\"]\ndraft = true\n+++\nbody text\n";
+        let (front, body) = split_front_matter(src);
+        assert_eq!(front.title.as_deref(), Some("Hello"));
+        assert_eq!(front.tags, ["rust", "web"]);
+        assert!(front.draft);
+        assert_eq!(body, "body text\n");
+    }
+
+    #[test]
+    fn skipped_header_level_warns() {
+        let cfg = CompilerConfig::default();
+        let (_, n) = issues(|| lint_header_levels(&parse("# Title\n\n### Detail", &cfg)));
+        assert_eq!(n, 1);
+        let (_, n) = issues(|| lint_header_levels(&parse("# Title\n\n## Part\n\n### Detail\n\n## Next", &cfg)));
+        assert_eq!(n, 0);
+    }
+
+    #[test]
+    fn indented_code_block_belongs_to_its_list_item() {
+        let cfg = CompilerConfig::default();
+        let html = render("- install it:\n    ```sh\n    cargo install minissg\n      --locked\n    ```\n- done", &cfg);
+        assert_eq!(html, "<ul><li>install it:<pre><code class=\"code-sh\">\
+            cargo install minissg\n  --locked\n</code></pre></li><li>done</li></ul>");
+    }
+
+    #[test]
+    fn adjacent_h1_and_h2_merge_into_a_title_block() {
+        let cfg = CompilerConfig { merge_subtitle: true, ..Default::default() };
+        assert_eq!(render("# Title\n## Subtitle\n\ntext", &cfg),
+            "<header class=\"title-block\"><h1 id=\"title\" class=\"title\">Title <a href=\"#title\" class=\"header-anchor\">#</a></h1>\
+            <h2 class=\"subtitle\">Subtitle</h2></header>\n<hr><br><p>text</p>\n");
+        // off by default
+        let html = render("# Title\n## Subtitle", &CompilerConfig::default());
+        assert!(html.starts_with("<h1 id=\"title\">") && html.contains("<h2 id=\"subtitle\">"), "{}", html);
+    }
+
+    #[test]
+    fn merged_title_is_listed_in_the_toc() {
+        let cfg = CompilerConfig { merge_subtitle: true, ..Default::default() };
+        let html = render("[[TOC]]\n\n# Title\n## Subtitle\n\n## Part", &cfg);
+        assert!(html.starts_with("<nav class=\"toc\"><ul><li><a href=\"#title\">Title</a>"), "{}", html);
+        assert!(html.contains("<a href=\"#part\">Part</a>") && !html.contains("#subtitle"), "{}", html);
+    }
+
+    #[test]
+    fn embedded_source_is_a_comment_that_can_not_end_early() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cfg = CompilerConfig { embed_source: true, ..site_config(dir.path()) };
+        cfg.templates.post = "{{content}}".to_string();
+        let post = dir.path().join("post.md");
+        let out = cfg.output_dir.join("post.html");
+        std::fs::write(&post, "a --> b").unwrap();
+        compile_post(&post, &out, &cfg).unwrap();
+        let html = std::fs::read_to_string(&out).unwrap();
+        assert_eq!(html, "<p>a --&gt; b</p>\n\n<!-- source:\na --&gt; b\n-->\n");
+        assert_eq!(html.matches("-->").count(), 1);
+    }
+
+    #[test]
+    fn code_wrap_adds_the_wrap_class() {
+        let src = "```\nlong line\n```";
+        assert!(render(src, &CompilerConfig::default()).starts_with("<pre>"));
+        let cfg = CompilerConfig { code_wrap: CodeWrap::Wrap, ..Default::default() };
+        assert!(render(src, &cfg).starts_with("<pre class=\"code-wrap\">"));
+    }
+
+    #[test]
+    fn derive_policy_takes_alt_text_from_the_file_name() {
+        let cfg = CompilerConfig { alt_text_policy: AltTextPolicy::Derive, ..Default::default() };
+        let (alt, n) = issues(|| image_alt("", "sunset.png", &cfg));
+        assert_eq!(alt, "sunset");
+        assert_eq!(n, 0);
+        assert_eq!(image_alt("", "photos/sunset_over-lake.jpg", &cfg), "sunset over lake");
+        // given alt text always wins
+        assert_eq!(image_alt("the sea", "sunset.png", &cfg), "the sea");
+    }
+
+    #[test]
+    fn citations_are_numbered_with_a_bibliography() {
+        let cfg = CompilerConfig {
+            bibliography: BTreeMap::from([
+                ("knuth".to_string(), "Knuth, The Art of Computer Programming".to_string()),
+                ("lamport".to_string(), "Lamport, LaTeX".to_string()),
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(render("see [@lamport] and [@knuth], again [@lamport]", &cfg),
+            "<p>see <sup class=\"citation\"><a href=\"#cite-lamport\">[1]</a></sup> \
+            and <sup class=\"citation\"><a href=\"#cite-knuth\">[2]</a></sup>, \
+            again <sup class=\"citation\"><a href=\"#cite-lamport\">[1]</a></sup></p>\n\
+            <section class=\"bibliography\">\n<h2>references</h2>\n<ol>\
+            <li id=\"cite-lamport\">Lamport, LaTeX</li>\
+            <li id=\"cite-knuth\">Knuth, The Art of Computer Programming</li>\
+            </ol>\n</section>\n");
+    }
+
+    #[test]
+    fn hostile_citation_keys_are_escaped() {
+        let cfg = CompilerConfig {
+            bibliography: BTreeMap::from([("a\"b".to_string(), "Quoted".to_string())]),
+            ..Default::default()
+        };
+        let (html, n) = issues(|| render("[@a<b>] and [@missing]", &cfg));
+        assert_eq!(html, "<p>[@a&lt;b&gt;] and [@missing]</p>\n");
+        assert_eq!(n, 1);
+        let mut state = RenderState { citations: vec!["a\"b".to_string()], ..Default::default() };
+        assert!(render_bibliography(&cfg, &mut state).contains("<li id=\"cite-a&quot;b\">Quoted</li>"));
+    }
+
+    #[test]
+    fn display_equations_are_numbered_in_order() {
+        let cfg = CompilerConfig { number_equations: true, math_backend: MathBackend::Off, ..Default::default() };
+        assert_eq!(render("\\[\na = b\n\\]\n\ntext\n\n\\[\nc = d\n\\]", &cfg),
+            "<div class=\"equation\"><div class=\"display-math\"><code class=\"math-source\">a = b\n</code></div>\
+            <span class=\"equation-number\">(1)</span></div>\
+            <p>text</p>\n\
+            <div class=\"equation\"><div class=\"display-math\"><code class=\"math-source\">c = d\n</code></div>\
+            <span class=\"equation-number\">(2)</span></div>");
+    }
+
+    #[test]
+    fn plain_text_output_is_readable() {
+        let src = "# Notes\n\nread [the docs](https://example.com) and $x^2$\n\n- one\n- two\n\n```\nlet x = 1;\n```";
+        let text = render_plain_text(&parse(src, &CompilerConfig::default()));
+        assert_eq!(text, "Notes\n=====\n\nread the docs (https://example.com) and $x^2$\n\n- one\n- two\n\nlet x = 1;\n");
+    }
+
+    #[test]
+    fn plain_text_posts_are_linked_as_txt() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = CompilerConfig { output_format: OutputFormat::PlainText, emit_sidecar: true, ..site_config(dir.path()) };
+        std::fs::create_dir_all(&cfg.posts_dir).unwrap();
+        std::fs::write(cfg.posts_dir.join("notes.md"), "some notes\n").unwrap();
+        compile_all(&cfg).unwrap();
+        assert!(cfg.output_dir.join("notes.txt").exists());
+        assert!(!cfg.output_dir.join("notes.html").exists());
+        for listing in ["index.html", "rss.xml", "sitemap.xml", "search.json", "notes.json"] {
+            let text = std::fs::read_to_string(cfg.output_dir.join(listing)).unwrap();
+            assert!(text.contains("/posts/notes.txt") && !text.contains("/posts/notes.html"), "{}: {}", listing, text);
+        }
+    }
+
+    #[test]
+    fn inline_spacing_is_kept_exactly() {
+        let cfg = CompilerConfig { math_backend: MathBackend::Off, ..Default::default() };
+        assert_eq!(render("a**b**c _d_ `e`f [g](/g)h[^1] i$x$ j[^2].", &cfg),
+            "<p>a<span class=\"bold\">b</span>c <span class=\"italic\">d</span> \
+            <span class=\"inline-code\">e</span>f <a href=\"/g\">g</a>h\
+            <sup id=\"ref1\"><a href=\"#fn1\">[1]</a></sup> \
+            i<span class=\"inline-math\"><code class=\"math-source\">x</code></span> \
+            j<sup id=\"ref2\"><a href=\"#fn2\">[2]</a></sup>.</p>\n");
+    }
+
+    #[test]
+    fn current_post_is_marked_in_the_nav() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cfg = CompilerConfig {
+            nav_links: vec![
+                ("home".to_string(), "/".to_string()),
+                ("about".to_string(), "/posts/about.html".to_string()),
+            ],
+            ..site_config(dir.path())
+        };
+        cfg.templates.post = "{{nav}}".to_string();
+        let post = dir.path().join("about.md");
+        let out = cfg.output_dir.join("about.html");
+        std::fs::write(&post, "hi").unwrap();
+        compile_post(&post, &out, &cfg).unwrap();
+        assert_eq!(std::fs::read_to_string(&out).unwrap(),
+            "<nav><ul><li><a href=\"/\">home</a></li>\
+            <li><a href=\"/posts/about.html\" aria-current=\"page\">about</a></li></ul></nav>");
+    }
+
+    #[test]
+    fn ampersands_in_urls_are_escaped_once() {
+        let cfg = CompilerConfig::default();
+        assert_eq!(render("[search](/find?a=1&b=2)", &cfg), "<p><a href=\"/find?a=1&amp;b=2\">search</a></p>\n");
+        assert_eq!(render("[search](/find?a=1&amp;b=2)", &cfg), "<p><a href=\"/find?a=1&amp;b=2\">search</a></p>\n");
+        assert_eq!(render("![chart](https://example.com/chart.png?w=1&h=2)", &cfg),
+            "<img src=\"https://example.com/chart.png?w=1&amp;h=2\" alt=\"chart\" class=\"image\">");
+    }
+
+    #[test]
+    fn per_post_code_lang_map_changes_the_class() {
+        let cfg = CompilerConfig { code_lang_aliases: BTreeMap::from([("sh".to_string(), "bash".to_string())]), ..Default::default() };
+        let (front, body) = split_front_matter("+++\ncode_lang_map = { text = \"sh\" }\n+++\n```text\nls\n```\n");
+        let html = render_post_body(parse(body, &cfg), &front, "", "", &cfg);
+        // remapped, then aliased
+        assert_eq!(html, "<pre><code class=\"code-bash\">ls\n</code></pre>");
+        assert_eq!(render("```text\nls\n```", &cfg), "<pre><code class=\"code-text\">ls\n</code></pre>");
+    }
+
+    #[test]
+    fn only_the_first_paragraph_is_the_lead() {
+        let cfg = CompilerConfig { lead_paragraph: true, ..Default::default() };
+        let html = render("## Intro\n\nfirst\n\nsecond", &cfg);
+        assert!(html.ends_with("<p class=\"lead\">first</p>\n<p>second</p>\n"), "{}", html);
+    }
+
+    #[test]
+    fn search_index_lists_published_posts() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = site_config(dir.path());
+        std::fs::create_dir_all(&cfg.posts_dir).unwrap();
+        std::fs::write(cfg.posts_dir.join("hello.md"), "+++\ntitle = \"Hello\"\ntags = [\"intro\"]\n+++\nsome **bold** words\n").unwrap();
+        std::fs::write(cfg.posts_dir.join("wip.md"), "+++\ndraft = true\n+++\nnot yet\n").unwrap();
+        compile_all(&cfg).unwrap();
+        let index: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(cfg.output_dir.join("search.json")).unwrap()).unwrap();
+        assert_eq!(index, serde_json::json!([{
+            "title": "Hello",
+            "url": "/posts/hello.html",
+            "tags": ["intro"],
+            "body": "some bold words\n",
+        }]));
+    }
+
+    #[test]
+    fn doubly_nested_quotes_nest_blockquotes() {
+        let cfg = CompilerConfig::default();
+        assert_eq!(render("> outer\n> > inner\n>>>> also inner\n> outer again", &cfg),
+            "<blockquote><p class=quote>outer</p>\
+            <blockquote><p class=quote>inner</p><p class=quote>also inner</p></blockquote>\
+            <p class=quote>outer again</p></blockquote>\n");
+    }
+
+    #[test]
+    fn code_lang_label_only_for_labeled_fences() {
+        let cfg = CompilerConfig { show_code_lang: true, ..Default::default() };
+        assert_eq!(render("```python\npass\n```", &cfg),
+            "<span class=\"code-lang\">python</span><pre><code class=\"code-python\">pass\n</code></pre>");
+        assert_eq!(render("```\npass\n```", &cfg),
+            "<pre><code class=\"code-\">pass\n</code></pre>");
+    }
+
+    #[test]
+    fn client_math_with_fallback_adds_a_noscript_svg() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = CompilerConfig {
+            math_backend: MathBackend::Client,
+            math_noscript_fallback: true,
+            ..site_config(dir.path())
+        };
+        seed_math_cache("x^2", false, "<svg>x squared</svg>", &cfg);
+        assert_eq!(render("$x^2$", &cfg),
+            "<p><span class=\"inline-math\"><span class=\"math-client\">\\(x^2\\)</span>\
+            <noscript><svg>x squared</svg></noscript></span></p>\n");
+    }
+
+    #[test]
+    fn inline_comments_are_stripped() {
+        let cfg = CompilerConfig::default();
+        assert_eq!(render("keep this %%not this%% and this", &cfg), "<p>keep this  and this</p>\n");
+        // literal inside code
+        assert_eq!(render("`a %%b%% c`", &cfg), "<p><span class=\"inline-code\">a %%b%% c</span></p>\n");
+    }
+
+    #[test]
+    fn footnote_backref_symbol_links_to_the_reference() {
+        let cfg = CompilerConfig {
+            footnote_backref_symbol: "⤴".to_string(),
+            footnote_id_prefix: false,
+            ..Default::default()
+        };
+        assert_eq!(render("[^1]: a note", &cfg),
+            "<p id=\"fn1\">a note <a href=\"#ref1\" class=\"footnote-backref\">⤴</a></p>");
+    }
+
+    #[test]
+    fn footnote_backref_symbol_is_escaped() {
+        let cfg = CompilerConfig { footnote_backref_symbol: "<b>up</b>".to_string(), ..Default::default() };
+        assert!(render("[^1]: a note", &cfg).contains("class=\"footnote-backref\">&lt;b&gt;up&lt;/b&gt;</a>"));
+    }
+
+    #[test]
+    fn large_images_get_a_smaller_thumbnail() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = CompilerConfig { image_thumbnails: Some(100), ..site_config(dir.path()) };
+        std::fs::create_dir_all(&cfg.images_dir).unwrap();
+        image::RgbImage::new(400, 300).save(cfg.images_dir.join("big.png")).unwrap();
+        assert_eq!(render("![big](big.png)", &cfg),
+            "<a href=\"/static/images/big.png\"><img src=\"/static/images/big.thumb.png\" alt=\"big\" class=\"image\" \
+            width=\"100\" height=\"75\"></a>");
+        let thumb = cfg.images_dir.join("big.thumb.png");
+        assert_eq!(image::image_dimensions(&thumb).unwrap(), (100, 75));
+    }
+
+    #[test]
+    fn empty_header_is_skipped() {
+        let cfg = CompilerConfig::default();
+        let (html, n) = issues(|| render("#\n\ntext\n\n## ##", &cfg));
+        assert_eq!(html, "<p>text</p>\n");
+        assert_eq!(n, 2);
+    }
+
+    #[test]
+    fn rtl_post_lists_carry_dir() {
+        let cfg = CompilerConfig::default();
+        let (front, body) = split_front_matter("---\ndir: rtl\n---\n1. one\n    1. nested\n");
+        assert_eq!(render_post_body(parse(body, &cfg), &front, "", "", &cfg),
+            "<ol dir=\"rtl\"><li>one<ol><li>nested</li></ol></li></ol>");
+    }
+
+    #[test]
+    fn unknown_text_direction_is_dropped() {
+        let cfg = CompilerConfig::default();
+        let (front, body) = split_front_matter("---\ndir: '\"><script>x</script>'\n---\n- one\n");
+        let (html, n) = issues(|| render_post_body(parse(body, &cfg), &front, "", "", &cfg));
+        assert_eq!(html, "<ul><li>one</li></ul>");
+        assert_eq!(n, 1);
+    }
+
+    #[test]
+    fn space_runs_are_kept_when_enabled() {
+        let src = "a  b   c d";
+        assert_eq!(render(src, &CompilerConfig::default()), "<p>a  b   c d</p>\n");
+        let cfg = CompilerConfig { preserve_spaces: true, ..Default::default() };
+        assert_eq!(render(src, &cfg), "<p>a&nbsp;&nbsp;b&nbsp;&nbsp;&nbsp;c d</p>\n");
+    }
+
+    #[test]
+    fn math_svg_class_is_added_to_the_root_svg() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = CompilerConfig { math_svg_class: Some("math-dark".to_string()), ..site_config(dir.path()) };
+        seed_math_cache("x", false, "<svg width='1pt'><g/></svg>", &cfg);
+        seed_math_cache("y\n", true, "<svg width='2pt'><svg/></svg>", &cfg);
+        assert_eq!(render("$x$\n\n\\[\ny\n\\]", &cfg),
+            "<p><span class=\"inline-math\"><svg class=\"math-dark\" width='1pt'><g/></svg></span></p>\n\
+            <div class=\"display-math\"><svg class=\"math-dark\" width='2pt'><svg/></svg></div>");
+    }
+
+    #[test]
+    fn math_svg_class_is_escaped() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = CompilerConfig { math_svg_class: Some("a\" onload=\"x".to_string()), ..site_config(dir.path()) };
+        seed_math_cache("x", false, "<svg/>", &cfg);
+        assert_eq!(render("$x$", &cfg),
+            "<p><span class=\"inline-math\"><svg class=\"a&quot; onload=&quot;x\"/></span></p>\n");
+    }
+
+    #[test]
+    fn unused_front_matter_key_warns() {
+        let mut cfg = CompilerConfig { strict_front_matter: true, ..Default::default() };
+        cfg.templates.post = "<p>{{author}}</p>{{content}}".to_string();
+        let (front, _) = split_front_matter("---\ntitle: Post\nauthor: me\nauther: me\n---\n");
+        let (_, n) = issues(|| lint_front_matter(&front, &cfg));
+        assert_eq!(n, 1);
+    }
+
+    #[test]
+    fn math_free_post_never_touches_math_tooling() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = site_config(dir.path());
+        let post = dir.path().join("post.md");
+        std::fs::write(&post, "# Prices\n\ncosts 5 dollars, `$HOME` is set\n").unwrap();
+        let (result, n) = issues(|| compile_post(&post, &cfg.output_dir.join("post.html"), &cfg));
+        assert!(result.is_ok());
+        assert_eq!(n, 0);
+        assert!(!cfg.cache_dir.join("math").exists());
+        assert!(!has_math(&parse("costs 5 dollars, `$HOME` is set", &cfg)));
+        assert!(has_math(&parse("- costs $x$ dollars", &cfg)));
+    }
+
+    #[test]
+    fn image_size_attributes_come_from_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = site_config(dir.path());
+        std::fs::create_dir_all(&cfg.images_dir).unwrap();
+        image::RgbImage::new(200, 100).save(cfg.images_dir.join("chart.png")).unwrap();
+        assert_eq!(render("![chart](chart.png)", &cfg),
+            "<img src=\"/static/images/chart.png\" alt=\"chart\" class=\"image\" width=\"200\" height=\"100\">");
+        assert_eq!(render("![chart](chart.png){50}", &cfg),
+            "<img src=\"/static/images/chart.png\" alt=\"chart\" class=\"image\" width=\"100\" height=\"50\" style=\"width: 50%;\">");
+        let (html, n) = issues(|| render("![gone](gone.png)", &cfg));
+        assert_eq!(html, "<img src=\"/static/images/gone.png\" alt=\"gone\" class=\"image\">");
+        assert_eq!(n, 1);
+    }
+
+    #[test]
+    fn edit_url_is_filled_with_the_file_name() {
+        let cfg = CompilerConfig {
+            edit_url_template: Some("https://github.com/user/repo/edit/main/posts/{{file}}".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(edit_url(Path::new("posts/hello-world.md"), &cfg),
+            "https://github.com/user/repo/edit/main/posts/hello-world.md");
+        assert_eq!(edit_url(Path::new("posts/hello-world.md"), &CompilerConfig::default()), "");
+    }
+
+    #[test]
+    fn indented_hash_line_continues_the_list_item() {
+        let cfg = CompilerConfig::default();
+        assert_eq!(render("- see the channel\n  #general for help\n- next", &cfg),
+            "<ul><li>see the channel #general for help</li><li>next</li></ul>");
+    }
+
+    #[test]
+    fn single_page_export_namespaces_ids_per_post() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = site_config(dir.path());
+        std::fs::create_dir_all(&cfg.posts_dir).unwrap();
+        for name in ["first", "second"] {
+            std::fs::write(cfg.posts_dir.join(format!("{}.md", name)), "## Notes\n\nsee[^1]\n\n[^1]: a note\n").unwrap();
+        }
+        let out = dir.path().join("all.html");
+        export_single_page(&out, &cfg);
+        let html = std::fs::read_to_string(&out).unwrap();
+        for id in ["first", "first-notes", "first-fn1", "first-ref1", "second", "second-notes", "second-fn1", "second-ref1"] {
+            assert!(html.contains(&format!("id=\"{}\"", id)), "no {} in {}", id, html);
+        }
+        let ids = Regex::new(r#"id="([^"]*)""#).unwrap().captures_iter(&html)
+            .map(|caps| caps[1].to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(ids.len(), ids.iter().collect::<BTreeSet<_>>().len(), "{:?}", ids);
+    }
+
+    #[test]
+    fn failed_math_is_shown_per_math_error_display() {
+        let err = MinissgError::Math("LaTeX failed: ! Missing $ inserted <inserted text>".to_string());
+        let shown = |display| render_math_error("\\foo < 1", &err, false,
+            &CompilerConfig { math_error_display: display, ..Default::default() });
+        assert_eq!(shown(MathErrorDisplay::Code), "<code class='latex-error'>LaTeX failed: ! Missing $ inserted &lt;inserted text&gt;</code>");
+        assert_eq!(shown(MathErrorDisplay::Source), "<span class=\"latex-source\">$\\foo &lt; 1$</span>");
+        assert_eq!(shown(MathErrorDisplay::Hidden), "");
+    }
+
+    #[test]
+    fn excerpt_cut_mid_bold_closes_the_span() {
+        let cfg = CompilerConfig::default();
+        let blocks = parse("one **two three four** five", &cfg);
+        assert_eq!(excerpt(&blocks, 3, "", &cfg), "one <span class=\"bold\">two three</span>…");
+        assert_eq!(excerpt(&blocks, 5, "", &cfg), "one <span class=\"bold\">two three four</span> five");
+    }
+
+    #[test]
+    fn ordered_and_unordered_items_share_levels() {
+        for indent_width in [2, 4] {
+            let cfg = CompilerConfig { indent_width, ..Default::default() };
+            for indent in [0, 2, 4, 8] {
+                let pad = " ".repeat(indent);
+                let ol = captures_ol_li(&format!("{}1. item", pad), &cfg).unwrap();
+                let ul = captures_ul_li(&format!("{}- item", pad), &cfg).unwrap();
+                assert_eq!(ol.level, ul.level);
+                assert_eq!(ol.level, indent / indent_width);
+            }
+        }
+    }
+
+    #[test]
+    fn youtube_shortcode_becomes_an_iframe() {
+        let cfg = CompilerConfig::default();
+        assert_eq!(render("{{youtube: dQw4w9WgXcQ}}", &cfg),
+            "<div class=\"video\"><iframe src=\"https://www.youtube.com/embed/dQw4w9WgXcQ\" title=\"youtube video\" \
+            frameborder=\"0\" allowfullscreen></iframe></div>\n");
+        let (html, n) = issues(|| render("{{tiktok: abc}}", &cfg));
+        assert_eq!(html, "<p>{{tiktok: abc}}</p>\n");
+        assert_eq!(n, 1);
+    }
+
+    #[test]
+    fn index_title_falls_back_to_the_first_h1() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = CompilerConfig { title_from_h1: true, ..site_config(dir.path()) };
+        std::fs::create_dir_all(&cfg.posts_dir).unwrap();
+        std::fs::write(cfg.posts_dir.join("post.md"), "# Real Title\n\ntext\n").unwrap();
+        compile_all(&cfg).unwrap();
+        let index = std::fs::read_to_string(cfg.output_dir.join("index.html")).unwrap();
+        assert!(index.contains("<li><a href=\"/posts/post.html\">Real Title</a></li>"), "{}", index);
+    }
+
+    #[test]
+    fn footnote_marker_flushes_footnotes_there() {
+        let cfg = CompilerConfig { footnote_placement: FootnotePlacement::Marker, footnote_backref_symbol: String::new(), ..Default::default() };
+        assert_eq!(render("one[^1]\n\n[^1]: first\n\n[^]\n\ntwo[^2]\n\n[^2]: second", &cfg),
+            "<p>one<sup id=\"ref1\"><a href=\"#fn1\">[1]</a></sup></p>\n\
+            <section class=\"footnotes\">\n<p id=\"fn1\"><a href=\"#ref1\">[1]</a> first</p>\n</section>\n\
+            <p>two<sup id=\"ref2\"><a href=\"#fn2\">[2]</a></sup></p>\n\
+            <section class=\"footnotes\">\n<p id=\"fn2\"><a href=\"#ref2\">[2]</a> second</p>\n</section>\n");
+    }
+
+    #[test]
+    fn footnote_placement_reads_the_requested_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join("minissg.toml");
+        for (value, placement) in [("none", FootnotePlacement::InPlace), ("in_place", FootnotePlacement::InPlace),
+                                   ("end", FootnotePlacement::End), ("marker", FootnotePlacement::Marker)] {
+            std::fs::write(&config, format!("footnote_placement = {:?}\n", value)).unwrap();
+            assert_eq!(load_config(&config, None, None, false, false).unwrap().footnote_placement, placement);
+        }
+        std::fs::write(&config, "footnote_placement = \"sideways\"\n").unwrap();
+        assert!(load_config(&config, None, None, false, false).is_err());
+    }
+
+    #[test]
+    fn pipe_table_aligns_columns_and_parses_cells() {
+        let html = render("| name | **n** | note |\n|:---|:--:|---:|\n| a | `x` | [l](u) |\n| b |",
+                          &CompilerConfig::default());
+        assert_eq!(html, "<table>\n<thead>\n\
+            <tr><th style=\"text-align: left;\">name</th>\
+            <th style=\"text-align: center;\"><span class=\"bold\">n</span></th>\
+            <th style=\"text-align: right;\">note</th></tr>\n</thead>\n<tbody>\n\
+            <tr><td style=\"text-align: left;\">a</td>\
+            <td style=\"text-align: center;\"><span class=\"inline-code\">x</span></td>\
+            <td style=\"text-align: right;\"><a href=\"u\">l</a></td></tr>\n\
+            <tr><td style=\"text-align: left;\">b</td><td style=\"text-align: center;\"></td>\
+            <td style=\"text-align: right;\"></td></tr>\n</tbody>\n</table>\n");
+    }
+
+    #[test]
+    fn slow_command_is_killed_at_the_timeout() {
+        let start = Instant::now();
+        let result = run_with_timeout(Command::new("sleep").arg("10"), Duration::from_millis(100));
+        assert!(start.elapsed() < Duration::from_secs(5));
+        let Err(err @ MinissgError::Command{..}) = result else { panic!("not a timeout: {:?}", result) };
+        assert_eq!(err.to_string(), "could not run sleep: timed out after 100ms");
+    }
+
+    #[test]
+    fn double_tildes_strike_through() {
+        let cfg = CompilerConfig::default();
+        assert_eq!(render("a ~~gone~~ b", &cfg), "<p>a <del>gone</del> b</p>\n");
+        assert_eq!(render("H~2~O and ~5", &cfg), "<p>H~2~O and ~5</p>\n");
+        assert_eq!(render("keep ~~the rest", &cfg), "<p>keep ~~the rest</p>\n");
+    }
+
+    #[test]
+    fn two_terms_share_one_definition() {
+        let cfg = CompilerConfig::default();
+        assert_eq!(render("color\ncolour\n: the look of a thing\n", &cfg),
+            "<dl>\n<dt>color</dt>\n<dt>colour</dt>\n<dd>the look of a thing</dd>\n</dl>\n");
+    }
+
+    #[test]
+    fn output_into_a_missing_nested_dir_is_written() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("www/posts/2024/post.html");
+        let (_, n) = issues(|| write_output(&path, "<p>hi</p>"));
+        assert_eq!(n, 0);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "<p>hi</p>");
+    }
+
+    #[test]
+    fn custom_container_uses_its_configured_tag() {
+        let cfg = CompilerConfig {
+            containers: BTreeMap::from([("aside".to_string(), ContainerSpec { tag: "aside".to_string(), class: "aside".to_string() })]),
+            ..Default::default()
+        };
+        assert_eq!(render(":::aside\nby the way\n\n- a list\n:::", &cfg),
+            "<aside class=\"aside\">\n<p>by the way</p>\n<ul><li>a list</li></ul></aside>\n");
+    }
+
+    #[test]
+    fn container_tag_must_be_an_element_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join("minissg.toml");
+        std::fs::write(&config, "[containers.note]\ntag = \"div onclick=x\"\nclass = \"note\"\n").unwrap();
+        let err = load_config(&config, None, None, false, false).expect_err("tag accepted");
+        assert!(err.to_string().contains("invalid tag"), "{}", err);
+        std::fs::write(&config, "[containers.note]\ntag = \"my-note\"\nclass = \"note\"\n").unwrap();
+        assert!(load_config(&config, None, None, false, false).is_ok());
+    }
+
+    #[test]
+    fn display_math_is_typeset_as_display() {
+        let cfg = CompilerConfig::default();
+        assert!(math_tex("\\frac{a}{b}", &cfg, true).contains("\n\\[\\frac{a}{b}\\]\n"));
+        assert!(math_tex("\\frac{a}{b}", &cfg, false).contains("\n$\\frac{a}{b}$\n"));
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = site_config(dir.path());
+        seed_math_cache("\\frac{a}{b}\n", true, "<svg/>", &cfg);
+        assert_eq!(render("\\[\n\\frac{a}{b}\n\\]", &cfg), "<div class=\"display-math\"><svg/></div>");
+    }
+
+    #[test]
+    #[ignore = "needs latex and dvisvgm"]
+    fn display_fraction_is_taller_than_inline() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = site_config(dir.path());
+        let height = |svg: &str| Regex::new(r"height='([\d.]+)pt'").unwrap()
+            .captures(svg)
+            .and_then(|caps| caps[1].parse::<f64>().ok())
+            .unwrap_or_else(|| panic!("no height in {}", svg));
+        let display = compile_math_to_svg("\\frac{a}{b}", &cfg, true).unwrap();
+        let inline = compile_math_to_svg("\\frac{a}{b}", &cfg, false).unwrap();
+        assert!(height(&display) > height(&inline), "{} vs {}", display, inline);
+    }
+
+    #[test]
+    fn references_inside_marks_are_parsed() {
+        let cfg = CompilerConfig::default();
+        assert_eq!(render("**see [a](http://b.c)** z", &cfg),
+            "<p><span class=\"bold\">see </span><span class=\"bold\"><a href=\"http://b.c\">a</a></span> z</p>\n");
+        assert_eq!(render("~~old [x](/y)~~", &cfg), "<p><del>old </del><del><a href=\"/y\">x</a></del></p>\n");
+        let html = render("*note[^1]*\n\n[^1]: the note", &cfg);
+        assert!(html.starts_with("<p><span class=\"bold\">note</span><span class=\"bold\"><sup id=\"ref1\"><a href=\"#fn1\">[1]</a></sup></span></p>\n"), "{}", html);
+    }
+
+    #[test]
+    fn mismatched_and_unterminated_marks_stay_balanced() {
+        let cfg = CompilerConfig::default();
+        // the first closer of an open mark closes it, the stray one is literal
+        assert_eq!(render("**a *b** c", &cfg), "<p><span class=\"bold\">a </span>b** c</p>\n");
+        assert_eq!(render("*_text*_", &cfg),
+            "<p><span class=\"bold\"><span class=\"italic\">text</span></span>_</p>\n");
+        assert_eq!(render("~~x", &cfg), "<p>~~x</p>\n");
+        assert_eq!(render("a **b", &cfg), "<p>a **b</p>\n");
+    }
+
+    #[test]
+    fn adjacent_code_blocks_become_tabs() {
+        let cfg = CompilerConfig { code_tabs: true, ..Default::default() };
+        assert_eq!(render("```rust\nfn f() {}\n```\n```python\ndef f(): pass\n```", &cfg),
+            "<div class=\"code-tabs\">\n<div class=\"tab-labels\" role=\"tablist\">\
+            <button role=\"tab\" aria-selected=\"true\" data-tab=\"0\">rust</button>\
+            <button role=\"tab\" aria-selected=\"false\" data-tab=\"1\">python</button></div>\n\
+            <div class=\"tab-panel\" role=\"tabpanel\" data-tab=\"0\">\
+            <pre><code class=\"code-rust\">fn f() {}\n</code></pre></div>\n\
+            <div class=\"tab-panel\" role=\"tabpanel\" data-tab=\"1\" hidden>\
+            <pre><code class=\"code-python\">def f(): pass\n</code></pre></div>\n</div>\n");
+        // a lone block stays as it is
+        assert!(render("```rust\nfn f() {}\n```", &cfg).starts_with("<pre"));
+    }
+
+    #[test]
+    fn slug_from_title_names_the_output_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = CompilerConfig { slug_from_title: true, ..site_config(dir.path()) };
+        std::fs::create_dir_all(&cfg.posts_dir).unwrap();
+        std::fs::write(cfg.posts_dir.join("2024-01-01.md"), "---\ntitle: My First Post\n---\nhello\n").unwrap();
+        compile_all(&cfg).unwrap();
+        assert!(cfg.output_dir.join("my-first-post.html").exists());
+        assert!(!cfg.output_dir.join("2024-01-01.html").exists());
+        let index = std::fs::read_to_string(cfg.output_dir.join("index.html")).unwrap();
+        assert!(index.contains("href=\"/posts/my-first-post.html\""), "{}", index);
+    }
+
+    #[test]
+    fn quote_depth_changes_open_and_close_blockquotes() {
+        let html = render("> one\n> > two\n> > > three\n> back", &CompilerConfig::default());
+        assert_eq!(html, "<blockquote><p class=quote>one</p>\
+            <blockquote><p class=quote>two</p>\
+            <blockquote><p class=quote>three</p></blockquote></blockquote>\
+            <p class=quote>back</p></blockquote>\n");
+    }
+
+    #[test]
+    fn math_template_without_placeholder_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let math = dir.path().join("math.tex");
+        std::fs::write(&math, "\\documentclass{standalone}\n\\begin{document}\n\\end{document}\n").unwrap();
+        let config = dir.path().join("minissg.toml");
+        std::fs::write(&config, format!("math_template = {:?}\n", math)).unwrap();
+        let err = load_config(&config, None, None, false, false).expect_err("template accepted");
+        assert!(err.to_string().contains("{{content}}"), "{}", err);
+
+        std::fs::write(&math, "\\begin{document}{{content}}\\end{document}\n").unwrap();
+        assert!(load_config(&config, None, None, false, false).is_ok());
+    }
+
+    #[test]
+    fn parenthesis_items_parse_as_an_ordered_list() {
+        let cfg = CompilerConfig::default();
+        let blocks = parse("1) one\n2) two", &cfg);
+        match blocks.as_slice() {
+            [Block::List(true, items)] => {
+                assert_eq!(items.len(), 2);
+                assert!(items.iter().all(|item| item.delimiter == ')'));
+            }
+            other => panic!("{:?}", other),
+        }
+        assert!(render("1) one\n2) two", &cfg).starts_with("<ol class=\"paren-delim\"><li>one</li>"));
+    }
+
+    #[test]
+    fn bare_urls_leave_trailing_punctuation_out() {
+        let cfg = CompilerConfig::default();
+        let link = |url: &str| format!("<a href=\"{0}\">{0}</a>", url);
+        assert_eq!(render("see https://x.com/a.", &cfg), format!("<p>see {}.</p>\n", link("https://x.com/a")));
+        assert_eq!(render("(at https://x.com), ok?", &cfg), format!("<p>(at {}), ok?</p>\n", link("https://x.com")));
+        assert_eq!(render("https://en.wikipedia.org/wiki/Rust_(language)!", &cfg),
+            format!("<p>{}!</p>\n", link("https://en.wikipedia.org/wiki/Rust_(language)")));
+    }
+
+    #[test]
+    fn bare_urls_inside_marks_keep_the_closer() {
+        let cfg = CompilerConfig::default();
+        assert_eq!(render("**https://x.com** after", &cfg),
+            "<p><span class=\"bold\"><a href=\"https://x.com\">https://x.com</a></span> after</p>\n");
+        assert_eq!(render("_see https://x.com/a_b_", &cfg),
+            "<p><span class=\"italic\">see </span><span class=\"italic\"><a href=\"https://x.com/a_b\">https://x.com/a_b</a></span></p>\n");
+        assert_eq!(render("~~https://x.com~~", &cfg),
+            "<p><del><a href=\"https://x.com\">https://x.com</a></del></p>\n");
+    }
+
+    #[test]
+    fn sidecar_holds_the_post_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = CompilerConfig { emit_sidecar: true, ..site_config(dir.path()) };
+        std::fs::create_dir_all(&cfg.posts_dir).unwrap();
+        std::fs::write(cfg.posts_dir.join("hello.md"),
+            "+++\ntitle = \"Hello\"\ndate = \"2024-01-02\"\ntags = [\"intro\"]\n+++\nthree short words\n").unwrap();
+        compile_all(&cfg).unwrap();
+        let sidecar: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(cfg.output_dir.join("hello.json")).unwrap()).unwrap();
+        assert_eq!(sidecar["title"], "Hello");
+        assert_eq!(sidecar["url"], "/posts/hello.html");
+        assert_eq!(sidecar["date"], "2024-01-02");
+        assert_eq!(sidecar["tags"], serde_json::json!(["intro"]));
+        assert_eq!(sidecar["draft"], false);
+        assert_eq!(sidecar["word_count"], 3);
+        assert!(sidecar["excerpt"].as_str().unwrap().contains("three short words"), "{}", sidecar);
+    }
+
+    #[test]
+    fn closing_hashes_are_stripped_from_headers() {
+        let cfg = CompilerConfig::default();
+        assert_eq!(render("## Title ##", &cfg), "<h2 id=\"title\">Title <a href=\"#title\" class=\"header-anchor\">#</a></h2>\n");
+        // a hash run that's part of the text stays
+        assert!(render("## C#", &cfg).contains(">C# <a"));
+    }
+
+    #[test]
+    fn setext_underlines_make_headers() {
+        let cfg = CompilerConfig::default();
+        assert_eq!(render("Title\n=====", &cfg), render("# Title", &cfg));
+        assert_eq!(render("Part\n--\n", &cfg), render("## Part", &cfg));
+        // the underline takes the whole paragraph above it
+        assert_eq!(render("some text\nPart\n---", &cfg), render("## some text Part", &cfg));
+    }
+
+    #[test]
+    fn short_or_trailed_underlines_are_not_setext() {
+        let cfg = CompilerConfig::default();
+        for src in ["para\n-\n", "para\n- \n", "para\n=\n", "para\n-- x\n", "para\n- item\n"] {
+            assert!(!render(src, &cfg).contains("<h"), "{:?}: {}", src, render(src, &cfg));
+        }
+    }
+
+    #[test]
+    fn code_blocks_keep_interior_blank_lines_only() {
+        let cfg = CompilerConfig::default();
+        assert_eq!(render("```rust\n\nfn a() {}\n\n\nfn b() {}\n\n```", &cfg),
+            "<pre><code class=\"code-rust\">fn a() {}\n\n\nfn b() {}\n</code></pre>");
+    }
+
+    #[test]
+    fn back_to_top_adds_the_anchor_and_the_link() {
+        let cfg = CompilerConfig { back_to_top: true, ..Default::default() };
+        let html = render("some text", &cfg);
+        assert!(html.starts_with("<a id=\"top\"></a>\n"), "{}", html);
+        assert!(html.ends_with("<a href=\"#top\" class=\"back-to-top\">back to top ↑</a>\n"), "{}", html);
+        assert!(!render("some text", &CompilerConfig::default()).contains("top"));
+    }
+
+    #[test]
+    fn deeper_headers_keep_their_level() {
+        let cfg = CompilerConfig::default();
+        assert_eq!(render("#### foo", &cfg), "<h4 id=\"foo\">foo <a href=\"#foo\" class=\"header-anchor\">#</a></h4>\n");
+        assert!(render("###### six", &cfg).starts_with("<h6 "));
+        assert!(render("# one", &cfg).contains("</h1>\n<hr><br>"));
+    }
+
+    #[test]
+    fn headers_get_unique_slug_ids_and_anchors() {
+        let html = render("## Setup\n\n## Setup\n\n## Hello, World!", &CompilerConfig::default());
+        assert_eq!(html, "<h2 id=\"setup\">Setup <a href=\"#setup\" class=\"header-anchor\">#</a></h2>\n\
+            <h2 id=\"setup-1\">Setup <a href=\"#setup-1\" class=\"header-anchor\">#</a></h2>\n\
+            <h2 id=\"hello-world\">Hello, World! <a href=\"#hello-world\" class=\"header-anchor\">#</a></h2>\n");
+    }
+
+    #[test]
+    fn file_name_caption_infers_the_language() {
+        let cfg = CompilerConfig::default();
+        let html = render("```:Dockerfile\nFROM rust\n```", &cfg);
+        assert!(html.contains("<code class=\"code-dockerfile\">FROM rust\n</code>"), "{}", html);
+        assert!(render("```:src/main.py\npass\n```", &cfg).contains("class=\"code-python\""));
+    }
+
+    #[test]
+    fn align_environment_is_not_wrapped_again() {
+        let cfg = CompilerConfig { math_template: "\\begin{document}\n{{content}}\n\\end{document}\n".to_string(), ..Default::default() };
+        let align = "\\begin{align}a &= b\\\\c &= d\\end{align}";
+        assert_eq!(math_tex(align, &cfg, true), format!("\\begin{{document}}\n{}\n\\end{{document}}\n", align));
+        assert!(math_tex("a = b", &cfg, true).contains("\\[a = b\\]"));
+        assert!(math_tex("a = b", &cfg, false).contains("$a = b$"));
+    }
+
+    #[test]
+    fn toc_nests_headers_by_level() {
+        let toc = |src: &str| render(&format!("[[TOC]]\n\n{}", src), &CompilerConfig::default())
+            .lines().next().unwrap().to_string();
+        assert_eq!(toc("# A\n\n## B\n\n## C\n\n# D"),
+            "<nav class=\"toc\"><ul><li><a href=\"#a\">A</a><ul><li><a href=\"#b\">B</a></li>\
+            <li><a href=\"#c\">C</a></li></ul></li><li><a href=\"#d\">D</a></li></ul></nav>");
+        // a skipped level gets an empty item instead of a <ul> right inside a <ul>
+        assert_eq!(toc("# A\n\n### B\n\n# C"),
+            "<nav class=\"toc\"><ul><li><a href=\"#a\">A</a><ul><li><ul><li><a href=\"#b\">B</a></li>\
+            </ul></li></ul></li><li><a href=\"#c\">C</a></li></ul></nav>");
+        assert_eq!(toc("### A\n\n## B"),
+            "<nav class=\"toc\"><ul><li><ul><li><a href=\"#a\">A</a></li></ul></li><li><a href=\"#b\">B</a></li></ul></nav>");
+    }
+
+    #[test]
+    fn toc_links_duplicate_headers_separately() {
+        let html = render("[[TOC]]\n\n## Setup\n\n## Setup", &CompilerConfig::default());
+        assert!(html.starts_with("<nav class=\"toc\"><ul><li><a href=\"#setup\">Setup</a></li>\
+            <li><a href=\"#setup-1\">Setup</a></li></ul></nav>\n"), "{}", html);
+        assert!(html.contains("<h2 id=\"setup-1\">"), "{}", html);
+    }
+
+    #[test]
+    fn header_right_after_text_ends_the_paragraph() {
+        let cfg = CompilerConfig::default();
+        assert_eq!(render("some text\n## Next", &cfg),
+            "<p>some text</p>\n<h2 id=\"next\">Next <a href=\"#next\" class=\"header-anchor\">#</a></h2>\n");
+    }
+
+    #[test]
+    fn highlighting_escapes_code_and_falls_back_to_plain() {
+        let src = "```rust\nlet s = \"<b>\";\n```\n\n```nosuchlang\n<b>\n```";
+        let plain = render(src, &CompilerConfig::default());
+        assert_eq!(plain, "<pre><code class=\"code-rust\">let s = &quot;&lt;b&gt;&quot;;\n</code></pre>\
+            <pre><code class=\"code-nosuchlang\">&lt;b&gt;\n</code></pre>");
+        let cfg = CompilerConfig { syntax_highlighting: true, ..Default::default() };
+        let html = render(src, &cfg);
+        if cfg!(feature = "highlight") {
+            assert!(html.starts_with("<pre><code class=\"code-rust\"><span style="));
+            assert!(html.contains("&quot;&lt;b&gt;&quot;") && !html.contains("<b>"));
+            assert!(html.ends_with("<pre><code class=\"code-nosuchlang\">&lt;b&gt;\n</code></pre>"));
+        } else {
+            assert_eq!(html, plain);
+        }
+    }
+
+    #[test]
+    fn rust_snippet_is_detected_as_rust() {
+        let src = "```\nfn main() {\n    let mut v = Vec::new();\n    println!(\"{:?}\", v);\n}\n```";
+        let cfg = CompilerConfig { autodetect_code_lang: true, ..Default::default() };
+        assert!(render(src, &cfg).contains("<code class=\"code-rust\">"));
+        // nothing to go on: left without a language
+        assert!(render("```\nhello\n```", &cfg).contains("<code class=\"code-\">"));
+        assert!(render(src, &CompilerConfig::default()).contains("<code class=\"code-\">"));
+    }
+
+    #[test]
+    fn math_cache_names_are_stable() {
+        let cfg = CompilerConfig { cache_dir: PathBuf::from("cache"), math_template: "{{content}}".to_string(), ..Default::default() };
+        // pinned: a different name here means every existing cache entry is orphaned
+        assert_eq!(math_cache_path("x^2", &cfg, false, ""), Path::new("cache/math/2f96f6f8ddd0aff7.svg"));
+        assert_ne!(math_cache_path("x^2", &cfg, true, ""), math_cache_path("x^2", &cfg, false, ""));
+        let font = CompilerConfig { math_font_package: Some("newtxmath".to_string()), ..cfg };
+        assert_ne!(math_cache_path("x^2", &font, false, ""), Path::new("cache/math/2f96f6f8ddd0aff7.svg"));
+    }
+
+    #[test]
+    fn math_locks_are_dropped_once_done() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = site_config(dir.path());
+        seed_math_cache("a", false, "<svg/>", &cfg);
+        let in_flight = |math: &str| MATH_IN_FLIGHT.lock().unwrap().contains_key(&math_cache_path(math, &cfg, false, ""));
+        // one cached and one failing (or compiled) expression, from a few threads at once
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    assert_eq!(render_math_to_svg("a", &cfg, false, "").unwrap(), "<svg/>");
+                    let _ = render_math_to_svg("\\notacommand", &cfg, false, "");
+                });
+            }
+        });
+        assert!(!in_flight("a"));
+        assert!(!in_flight("\\notacommand"));
+    }
+
+    #[test]
+    fn empty_list_items_follow_the_policy() {
+        let src = "- one\n- \n- two";
+        let (html, warned) = issues(|| render(src, &CompilerConfig::default()));
+        assert_eq!((html.as_str(), warned), ("<ul><li>one</li><li>two</li></ul>", 1));
+        let cfg = CompilerConfig { empty_list_items: EmptyListItemPolicy::Keep, ..Default::default() };
+        let (html, warned) = issues(|| render(src, &cfg));
+        assert_eq!((html.as_str(), warned), ("<ul><li>one</li><li></li><li>two</li></ul>", 1));
+    }
+
+    #[test]
+    fn math_font_package_goes_into_the_preamble() {
+        let cfg = CompilerConfig {
+            math_template: "\\documentclass{standalone}\n\\begin{document}\n{{content}}\n\\end{document}\n".to_string(),
+            math_font_package: Some("newtxmath".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(math_tex("x", &cfg, false),
+            "\\documentclass{standalone}\n\\usepackage{newtxmath}\n\\begin{document}\n$x$\n\\end{document}\n");
+    }
+
+    #[test]
+    fn up_to_date_posts_are_skipped_until_the_source_or_a_template_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cfg = site_config(dir.path());
+        let template = dir.path().join("template.html");
+        std::fs::write(&template, "").unwrap();
+        cfg.template_paths = vec![template.clone()];
+        std::fs::create_dir_all(&cfg.posts_dir).unwrap();
+        let post = cfg.posts_dir.join("a.md");
+        std::fs::write(&post, "hello").unwrap();
+        let touch = |path: &Path, t: SystemTime|
+            std::fs::File::options().write(true).open(path).unwrap().set_modified(t).unwrap();
+        let (past, future) = (SystemTime::now() - Duration::from_secs(60), SystemTime::now() + Duration::from_secs(60));
+        touch(&post, past);
+        touch(&template, past);
+        compile_file(&post, &cfg).unwrap();
+
+        // a newer output is left alone unless something it was built from changed since
+        let out = post_output_path(&post, &cfg);
+        let rebuilt = |cfg: &CompilerConfig| {
+            std::fs::write(&out, "stale").unwrap();
+            compile_file(&post, cfg).unwrap();
+            std::fs::read_to_string(&out).unwrap() != "stale"
+        };
+        assert!(!rebuilt(&cfg));
+        touch(&post, future);
+        assert!(rebuilt(&cfg));
+        touch(&post, past);
+        touch(&template, future);
+        assert!(rebuilt(&cfg));
+        touch(&template, past);
+        assert!(!rebuilt(&cfg));
+        cfg.force = true;
+        assert!(rebuilt(&cfg));
+    }
+
+    #[test]
+    fn config_file_sets_paths_and_templates() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join("minissg.toml");
+        let defaults = CompilerConfig::default();
+        let cfg = load_config(&config, None, None, false, false).unwrap();
+        assert_eq!((cfg.posts_dir, cfg.images_dir, cfg.output_dir),
+                   (defaults.posts_dir, defaults.images_dir, defaults.output_dir));
+
+        let (post, math) = (dir.path().join("post.html"), dir.path().join("math.tex"));
+        std::fs::write(&post, "<main>{{content}}</main>").unwrap();
+        std::fs::write(&math, "\\begin{document}{{content}}\\end{document}").unwrap();
+        std::fs::write(&config, format!("posts_dir = \"notes\"\nimages_dir = \"pics\"\noutput_dir = \"site\"\n\
+            post_template = {:?}\nmath_template = {:?}\n", post, math)).unwrap();
+        let cfg = load_config(&config, None, None, false, false).unwrap();
+        assert_eq!((cfg.posts_dir, cfg.images_dir, cfg.output_dir),
+                   (PathBuf::from("notes"), PathBuf::from("pics"), PathBuf::from("site")));
+        assert_eq!(cfg.templates.post, "<main>{{content}}</main>");
+        assert_eq!(cfg.math_template, "\\begin{document}{{content}}\\end{document}");
+        assert_eq!(cfg.template_paths, [post, math]);
+
+        // the command line still picks the posts dir
+        let cfg = load_config(&config, Some(PathBuf::from("drafts")), None, false, false).unwrap();
+        assert_eq!(cfg.posts_dir, PathBuf::from("drafts"));
+    }
+
+    #[test]
+    fn second_h1_warns_and_can_be_demoted() {
+        let lint = |cfg: &CompilerConfig| issues(|| {
+            let mut blocks = parse("# One\n\ntext\n\n# Two", cfg);
+            lint_multiple_h1s(&mut blocks, cfg);
+            render_document(blocks, cfg)
+        });
+        let (html, warned) = lint(&CompilerConfig::default());
+        assert!(html.contains("<h1 id=\"two\">"), "{}", html);
+        assert_eq!(warned, 1);
+        let (html, warned) = lint(&CompilerConfig { demote_extra_h1s: true, ..Default::default() });
+        assert!(html.contains("<h1 id=\"one\">") && html.contains("<h2 id=\"two\">"), "{}", html);
+        assert_eq!(warned, 1);
+    }
+
+    #[test]
+    fn global_math_cache_is_shared_between_posts() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = site_config(dir.path());
+        let svg = "<svg>cached</svg>";
+        write_cache(&math_cache_path("a^2", &cfg, false, "post-a"), svg);
+        assert_eq!(render_math_to_svg("a^2", &cfg, false, "post-b").unwrap(), svg);
+
+        let cfg = CompilerConfig { math_cache_scope: MathCacheScope::PerPost, ..cfg };
+        assert_ne!(math_cache_path("a^2", &cfg, false, "post-a"), math_cache_path("a^2", &cfg, false, "post-b"));
+    }
+
+    #[test]
+    #[ignore = "needs latex and dvisvgm"]
+    fn global_math_cache_entry_is_reused_by_the_next_post() {
+        let dir = tempfile::tempdir().unwrap();
+        // every svg under dir, at any depth
+        fn svgs(dir: &Path) -> Vec<PathBuf> {
+            let mut found = Vec::new();
+            for entry in std::fs::read_dir(dir).into_iter().flatten().flatten() {
+                let path = entry.path();
+                if path.is_dir() {
+                    found.extend(svgs(&path));
+                } else if path.extension().is_some_and(|e| e == "svg") {
+                    found.push(path);
+                }
+            }
+            found
+        }
+        let entries = |cfg: &CompilerConfig| svgs(&cfg.cache_dir.join("math"));
+        let compile = |name: &str, cfg: &CompilerConfig| {
+            let post = dir.path().join(format!("{}.md", name));
+            std::fs::write(&post, "square $a^2$\n").unwrap();
+            compile_post(&post, &cfg.output_dir.join(name).with_extension("html"), cfg).unwrap();
+            std::fs::read_to_string(cfg.output_dir.join(name).with_extension("html")).unwrap()
+        };
+
+        let cfg = site_config(dir.path());
+        let a = compile("post-a", &cfg);
+        let found = entries(&cfg);
+        let [entry] = found.as_slice() else { panic!("{:?}", found) };
+        let compiled = std::fs::metadata(entry).unwrap().modified().unwrap();
+        let b = compile("post-b", &cfg);
+        // post b found post a's svg instead of compiling its own
+        assert_eq!(&entries(&cfg), &found);
+        assert_eq!(std::fs::metadata(entry).unwrap().modified().unwrap(), compiled);
+        assert!(a.contains("<svg") && b.contains(&std::fs::read_to_string(entry).unwrap()));
+
+        let cfg = CompilerConfig { math_cache_scope: MathCacheScope::PerPost, cache_dir: dir.path().join("per-post"), ..cfg };
+        compile("post-a", &cfg);
+        compile("post-b", &cfg);
+        assert_eq!(entries(&cfg).len(), 2);
+    }
+
+    #[test]
+    fn math_cache_scope_reads_both_spellings() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join("minissg.toml");
+        for value in ["per-post", "per_post"] {
+            std::fs::write(&config, format!("math_cache_scope = {:?}\n", value)).unwrap();
+            assert_eq!(load_config(&config, None, None, false, false).unwrap().math_cache_scope, MathCacheScope::PerPost);
+        }
+    }
+
+    #[test]
+    fn link_title_becomes_the_title_attribute() {
+        let html = render("[x](/u \"hover\")", &CompilerConfig::default());
+        assert!(html.contains("href=\"/u\""), "{}", html);
+        assert!(html.contains("title=\"hover\""), "{}", html);
+        assert!(html.contains(">x</a>"), "{}", html);
+    }
+
+    #[test]
+    fn image_width_takes_a_unit() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = site_config(dir.path());
+        std::fs::create_dir_all(&cfg.images_dir).unwrap();
+        image::RgbImage::new(200, 100).save(cfg.images_dir.join("chart.png")).unwrap();
+        assert_eq!(render("![chart](chart.png){300px}", &cfg),
+            "<img src=\"/static/images/chart.png\" alt=\"chart\" class=\"image\" width=\"300\" height=\"150\" style=\"width: 300px;\">");
+        assert_eq!(render("![chart](chart.png){50%}", &cfg),
+            "<img src=\"/static/images/chart.png\" alt=\"chart\" class=\"image\" width=\"100\" height=\"50\" style=\"width: 50%;\">");
+    }
+
+    #[cfg(feature = "serve")]
+    #[test]
+    fn served_paths_stay_inside_the_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("www");
+        std::fs::create_dir_all(root.join("posts")).unwrap();
+        std::fs::write(root.join("index.html"), "").unwrap();
+        std::fs::write(root.join("posts/a.html"), "").unwrap();
+        std::fs::write(dir.path().join("secret.txt"), "").unwrap();
+        assert!(resolve_request_path(&root, "/").is_some_and(|f| f.ends_with("index.html")));
+        assert!(resolve_request_path(&root, "/posts/a.html").is_some());
+        assert!(resolve_request_path(&root, "/posts/../index.html").is_some());
+        assert!(resolve_request_path(&root, "/../secret.txt").is_none());
+        assert!(resolve_request_path(&root, "/posts/..\\..\\secret.txt").is_none());
+        assert!(resolve_request_path(&root, "/posts/missing.html").is_none());
+    }
+
+    #[test]
+    fn adjacent_footnote_refs_get_a_separator() {
+        let html = render("a[^1][^2]", &CompilerConfig::default());
+        assert!(html.contains("</sup><sup>,</sup><sup"), "{}", html);
+        let cfg = CompilerConfig { footnote_ref_separator: String::new(), ..Default::default() };
+        assert!(!render("a[^1][^2]", &cfg).contains(","));
+    }
+
+    #[test]
+    fn tag_feed_holds_only_the_tagged_posts() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = site_config(dir.path());
+        std::fs::create_dir_all(&cfg.posts_dir).unwrap();
+        std::fs::write(cfg.posts_dir.join("tagged.md"), "+++\ntitle = \"Tagged\"\ntags = [\"rust\"]\n+++\nyes\n").unwrap();
+        std::fs::write(cfg.posts_dir.join("other.md"), "+++\ntitle = \"Other\"\ntags = [\"go\"]\n+++\nno\n").unwrap();
+        compile_all(&cfg).unwrap();
+        let feed = std::fs::read_to_string(cfg.output_dir.join("tags").join("rust.xml")).unwrap();
+        assert!(feed.contains("/posts/tagged.html"), "{}", feed);
+        assert!(!feed.contains("/posts/other.html"), "{}", feed);
+        assert_eq!(feed.matches("<item>").count(), 1, "{}", feed);
+    }
+
+    #[test]
+    fn site_feed_lists_posts_newest_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = CompilerConfig {
+            site_url: "https://example.com".to_string(),
+            site_title: "Notes & more".to_string(),
+            ..site_config(dir.path())
+        };
+        std::fs::create_dir_all(&cfg.posts_dir).unwrap();
+        std::fs::write(cfg.posts_dir.join("old.md"),
+                       "+++\ntitle = \"Old\"\ndate = \"2020-01-01\"\n+++\nFirst **para**.\n\nSecond para.\n").unwrap();
+        std::fs::write(cfg.posts_dir.join("new.md"), "+++\ntitle = \"New\"\ndate = \"2024-03-05\"\n+++\nnew\n").unwrap();
+        // no front matter: named after the file, dated by its mtime
+        let undated = cfg.posts_dir.join("undated.md");
+        std::fs::write(&undated, "mid\n").unwrap();
+        let mtime = 1_654_041_600;  // 2022-06-01
+        std::fs::File::options().write(true).open(&undated).unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(mtime)).unwrap();
+        compile_all(&cfg).unwrap();
+
+        let feed = std::fs::read_to_string(cfg.output_dir.join("rss.xml")).unwrap();
+        assert!(feed.contains("<title>Notes &amp; more</title>\n<link>https://example.com/</link>"), "{}", feed);
+        let titles = feed.match_indices("<item>\n<title>")
+            .map(|(i, m)| feed[i + m.len()..].split('<').next().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(titles, ["New", "undated", "Old"]);
+        assert!(feed.contains("<link>https://example.com/posts/old.html</link>"), "{}", feed);
+        assert!(feed.contains("<description>First para.</description>"), "{}", feed);
+        assert!(feed.contains(&format!("<pubDate>{}</pubDate>", rfc822_date(mtime as i64))), "{}", feed);
+        assert!(feed.contains("<pubDate>Tue, 05 Mar 2024 00:00:00 +0000</pubDate>"), "{}", feed);
+    }
+
+    #[test]
+    fn sitemap_lists_published_pages_with_their_dates() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = CompilerConfig { site_url: "https://example.com/".to_string(), ..site_config(dir.path()) };
+        std::fs::create_dir_all(&cfg.posts_dir).unwrap();
+        let post = cfg.posts_dir.join("a.md");
+        std::fs::write(&post, "+++\ntags = [\"rust\"]\n+++\nhi\n").unwrap();
+        std::fs::File::options().write(true).open(&post).unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(1_654_041_600)).unwrap();
+        std::fs::write(cfg.posts_dir.join("draft.md"), "+++\ndraft = true\n+++\nwip\n").unwrap();
+        compile_all(&cfg).unwrap();
+
+        let sitemap = std::fs::read_to_string(cfg.output_dir.join("sitemap.xml")).unwrap();
+        let day = "<lastmod>2022-06-01</lastmod>\n";
+        assert!(sitemap.contains(&format!("<url>\n<loc>https://example.com/posts/index.html</loc>\n{}</url>", day)), "{}", sitemap);
+        assert!(sitemap.contains(&format!("<url>\n<loc>https://example.com/posts/tags/rust.html</loc>\n{}</url>", day)), "{}", sitemap);
+        assert!(sitemap.contains(&format!("<url>\n<loc>https://example.com/posts/a.html</loc>\n{}</url>", day)), "{}", sitemap);
+        assert!(!sitemap.contains("draft"), "{}", sitemap);
+        assert_eq!(sitemap.matches("<url>").count(), 3, "{}", sitemap);
+    }
+
+    #[test]
+    fn broken_post_is_skipped_and_the_rest_compile() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = site_config(dir.path());
+        std::fs::create_dir_all(&cfg.posts_dir).unwrap();
+        std::fs::write(cfg.posts_dir.join("good.md"), "fine\n").unwrap();
+        std::fs::write(cfg.posts_dir.join("broken.md"), b"not \xff utf-8\n").unwrap();
+        compile_all(&cfg).unwrap();
+        assert!(cfg.output_dir.join("good.html").exists());
+        assert!(!cfg.output_dir.join("broken.html").exists());
+        let index = std::fs::read_to_string(cfg.output_dir.join("index.html")).unwrap();
+        assert!(index.contains("/posts/good.html") && !index.contains("/posts/broken.html"), "{}", index);
+
+        let cfg = CompilerConfig { on_post_error: PostErrorPolicy::Abort, ..cfg };
+        assert!(compile_all(&cfg).is_err());
+    }
+
+    #[test]
+    fn left_display_math_gets_the_left_class() {
+        let cfg = CompilerConfig { display_math_align: DisplayMathAlign::Left, math_backend: MathBackend::Off, ..Default::default() };
+        assert_eq!(render("\\[\na = b\n\\]", &cfg),
+            "<div class=\"display-math left\"><code class=\"math-source\">a = b\n</code></div>");
+        let cfg = CompilerConfig { math_backend: MathBackend::Off, ..Default::default() };
+        assert!(render("\\[\na = b\n\\]", &cfg).starts_with("<div class=\"display-math\">"));
+    }
+
+    #[test]
+    fn custom_tag_template_is_used_for_tag_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cfg = site_config(dir.path());
+        cfg.templates.tag = Some("<main class=\"tag-page\"><h1>{{title}}</h1>{{content}}</main>".to_string());
+        std::fs::create_dir_all(&cfg.posts_dir).unwrap();
+        std::fs::write(cfg.posts_dir.join("hello.md"), "+++\ntitle = \"Hello\"\ntags = [\"intro\"]\n+++\nhi\n").unwrap();
+        compile_all(&cfg).unwrap();
+        let page = std::fs::read_to_string(cfg.output_dir.join("tags").join("intro.html")).unwrap();
+        assert!(page.starts_with("<main class=\"tag-page\"><h1>"), "{}", page);
+        assert!(page.contains("/posts/hello.html"), "{}", page);
+        // the index keeps the post template
+        let index = std::fs::read_to_string(cfg.output_dir.join("index.html")).unwrap();
+        assert!(!index.contains("tag-page"), "{}", index);
+    }
+
+    #[test]
+    fn list_of_figures_links_every_figure() {
+        let cfg = CompilerConfig { number_figures: true, ..Default::default() };
+        let html = render("{{lof}}\n\n![A cat](cat.png)\n\n![A dog](dog.png)", &cfg);
+        assert!(html.starts_with("<nav class=\"lof\"><ul>\
+            <li><a href=\"#fig-1\">Figure 1</a>: A cat</li>\
+            <li><a href=\"#fig-2\">Figure 2</a>: A dog</li>\
+            </ul></nav>\n"), "{}", html);
+        assert!(html.contains("<figure id=\"fig-2\" class=\"figure\">"), "{}", html);
+        assert!(!render("{{lof}}\n\ntext", &cfg).contains("lof"));
+    }
+
+    #[test]
+    fn missing_latex_is_not_warned_per_expression() {
+        if Command::new("latex").arg("--version").output().is_ok() {
+            return;  // only meaningful without a tex install
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = site_config(dir.path());
+        let (results, warned) = issues(|| ["a", "b^2", "c"].map(|math| compile_math_to_svg(math, &cfg, false)));
+        assert!(results.iter().all(|r| matches!(r, Err(MinissgError::ToolMissing(_)))), "{:?}", results);
+        assert_eq!(warned, 0);
+        // each formula still keeps the post out of the cache, and is shown as source
+        let (html, n) = issues(|| render("$a$ and $b^2$ and $c$", &cfg));
+        assert_eq!(html.matches("<code class=\"math-source\">").count(), 3);
+        assert_eq!(n, 3);
+    }
+
+    #[test]
+    fn inline_code_with_html_is_escaped() {
+        assert_eq!(render("see `<a href=\"x\">*not* bold</a>` here", &CompilerConfig::default()),
+            "<p>see <span class=\"inline-code\">&lt;a href=&quot;x&quot;&gt;*not* bold&lt;/a&gt;</span> here</p>\n");
+    }
+}